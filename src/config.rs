@@ -1,48 +1,1188 @@
 use serde::{Deserialize, Serialize};
+use axum::http::{HeaderName, Method};
+use std::collections::BTreeMap;
+use std::borrow::Cow;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use crate::llms::balanced::BalanceStrategy;
+use crate::llms::{EmbeddingModel, LlmModel};
+use crate::llms::capabilities::{Capability, ModelCapabilities};
+use crate::llms::cache::ResponseCache;
+use crate::llms::local_embedding::HashingEmbeddingModel;
+use crate::llms::openai::OpenAiEmbeddingModel;
+use crate::llms::semantic_cache::SemanticCache;
+use crate::llms::readiness::ReadinessChecker;
+use std::sync::Arc;
+use crate::llms::circuit_breaker::CircuitBreakerSettings;
+use crate::llms::model_collection::BUILTIN_MODELS;
+use crate::llms::error::ErrorClass;
+use crate::llms::tokenizer::{self, OverflowAction};
+use tower_http::cors::{AllowHeaders, AllowOrigin, CorsLayer};
+use crate::secret::{redact_credentials, ApiKey, Secret};
+
+pub mod source;
 
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub port: u16,
+    /// Address or host name the API server listens on, together with `port`.
+    pub bind_address: String,
+    /// Listen on this Unix domain socket instead of `bind_address` and `port`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unix_socket: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    pub cors: CorsConfig,
+    /// Largest request body the API accepts.
+    pub max_body_bytes: usize,
+    pub timeouts: TimeoutConfig,
+    pub prompt_limits: PromptLimitsConfig,
+    /// How long in-flight requests may take to finish on shutdown.
+    pub shutdown_timeout_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ollama: Option<OllamaConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub balanced_models: Vec<BalancedModelConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fallback_models: Vec<FallbackModelConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub router_models: Vec<RouterModelConfig>,
+    pub discovery: DiscoveryConfig,
+    /// Other names each model can be asked for by, keyed by model name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// Model answering `/query` requests that name none.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    /// Limits, features and prices by model name, completing or correcting
+    /// the built-in catalog.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub model_info: BTreeMap<String, ModelCapabilities>,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub cache: CacheConfig,
+    pub semantic_cache: SemanticCacheConfig,
+    /// Coalesce identical concurrent `/query` requests into one upstream call.
+    pub deduplicate_requests: bool,
+    /// Reload the models when the config file changes.
+    pub watch_config: bool,
+    pub logging: LoggingConfig,
+    pub telemetry: TelemetryConfig,
+    pub readiness: ReadinessConfig,
+    pub api_keys: ApiKeysConfig,
+    pub secrets: SecretsConfig,
+    pub admin: AdminConfig,
+}
+
+/// Certificate and key for serving the API over HTTPS.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain, leaf certificate first.
+    pub cert: PathBuf,
+    /// PEM file with the private key.
+    pub key: PathBuf,
+    /// Load the certificate and key again when either file changes.
+    #[serde(default = "default_true")]
+    pub watch: bool,
+}
+
+/// Cross-origin access for browser-based clients. Without origins no CORS
+/// headers are sent and browsers block cross-origin calls.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Origins allowed to call the API, e.g. `https://tools.example.com`, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    /// How long browsers may cache a preflight response.
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            max_age_secs: 600,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn build(&self) -> Option<CorsLayer> {
+        if self.allowed_origins.is_empty() {
+            return None;
+        }
+
+        let origins = if self.allowed_origins.iter().any(|origin| origin == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(self.allowed_origins.iter().filter_map(|origin| origin.parse().ok()))
+        };
+        Some(CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers(AllowHeaders::mirror_request())
+            .expose_headers([HeaderName::from_static("x-request-id")])
+            .max_age(Duration::from_secs(self.max_age_secs)))
+    }
+}
+
+/// Limits on how long upstream calls of `/query` and `/embeddings` may take.
+/// For `/query` the model timeouts apply to each provider call, so a call
+/// that times out counts against its circuit breaker and lets a fallback
+/// model answer.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct TimeoutConfig {
+    /// Used for models without a timeout of their own.
+    pub request_ms: u64,
+    /// Upper bound for timeouts requested with `TimeoutMs`.
+    pub max_ms: u64,
+    /// Timeouts of individual models, by the name requests use.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub models: BTreeMap<String, u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            request_ms: 120_000,
+            max_ms: 600_000,
+            models: BTreeMap::new(),
+        }
+    }
+}
+
+impl TimeoutConfig {
+    /// The timeout of each call to the provider behind `model`.
+    pub fn for_model(&self, model: &str) -> Duration {
+        Duration::from_millis(self.models.get(model).copied().unwrap_or(self.request_ms))
+    }
+
+    /// The timeout for a request to `model`: the one the request asks for,
+    /// up to `max_ms`, or else the model's or the default one.
+    pub fn for_request(&self, model: &str, requested_ms: Option<u64>) -> Duration {
+        match requested_ms {
+            Some(requested_ms) => Duration::from_millis(requested_ms.min(self.max_ms)),
+            None => self.for_model(model),
+        }
+    }
+
+    /// How long a `/query` request may take overall, fallbacks included: the
+    /// time it asks for, up to `max_ms`, or else `max_ms`.
+    pub fn deadline(&self, requested_ms: Option<u64>) -> Duration {
+        Duration::from_millis(requested_ms.unwrap_or(self.max_ms).min(self.max_ms))
+    }
+}
+
+/// How prompts longer than a model's context window are handled before
+/// calling upstream.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct PromptLimitsConfig {
+    pub on_overflow: OverflowAction,
+    /// Tokens of the context window kept free for the answer, at most the
+    /// model's output limit.
+    pub reserve_output_tokens: u32,
+}
+
+impl Default for PromptLimitsConfig {
+    fn default() -> Self {
+        Self {
+            on_overflow: OverflowAction::Reject,
+            reserve_output_tokens: 1024,
+        }
+    }
+}
+
+impl PromptLimitsConfig {
+    /// The prompt to send to `model`, which is registered as `model_name`,
+    /// or why it cannot be sent.
+    pub fn fit<'a>(&self, prompt: &'a str, model_name: &str, model: &dyn LlmModel) -> Result<Cow<'a, str>, String> {
+        tokenizer::fit_prompt(prompt, model_name, model, self.on_overflow, self.reserve_output_tokens)
+    }
+
+    /// Most prompt tokens `model` takes, if its context window is known.
+    pub fn max_prompt_tokens(&self, model: &dyn LlmModel) -> Option<usize> {
+        tokenizer::max_prompt_tokens(model, self.reserve_output_tokens)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OllamaConfig {
+    #[serde(default = "default_ollama_host")]
+    pub host: String,
+    #[serde(default)]
+    pub models: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embedding_models: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    #[serde(rename = "openai")]
+    OpenAi,
+    Anthropic,
+    Ollama,
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ProviderKind::OpenAi => "openai",
+            ProviderKind::Anthropic => "anthropic",
+            ProviderKind::Ollama => "ollama",
+        })
+    }
+}
+
+/// One credential/endpoint combination serving a model.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BackendConfig {
+    pub provider: ProviderKind,
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<Secret>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+/// Registers the models a provider lists, e.g. new OpenAI models as they
+/// are released. Providers without rules are not queried.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// How often the providers' model lists are fetched again; 0 fetches
+    /// them only at startup and when these settings change.
+    pub refresh_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub openai: Option<DiscoveryRules>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anthropic: Option<DiscoveryRules>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ollama: Option<DiscoveryRules>,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            refresh_secs: 3600,
+            openai: None,
+            anthropic: None,
+            ollama: None,
+        }
+    }
+}
+
+impl DiscoveryConfig {
+    pub fn enabled(&self) -> bool {
+        self.openai.is_some() || self.anthropic.is_some() || self.ollama.is_some()
+    }
+
+    /// The rules of each provider that has some.
+    pub fn providers(&self) -> impl Iterator<Item = (ProviderKind, &DiscoveryRules)> {
+        [
+            (ProviderKind::OpenAi, &self.openai),
+            (ProviderKind::Anthropic, &self.anthropic),
+            (ProviderKind::Ollama, &self.ollama),
+        ]
+        .into_iter()
+        .filter_map(|(provider, rules)| rules.as_ref().map(|rules| (provider, rules)))
+    }
+}
+
+/// Which of a provider's models to register and under which name.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DiscoveryRules {
+    /// Model ids to register, as exact names or globs with `*` and `?`.
+    pub include: Vec<String>,
+    /// Model ids to leave out even though `include` matches them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Name to register a model under; `{model}` is the model id and
+    /// `{provider}` the provider, e.g. `{provider}/{model}`.
+    #[serde(default = "default_discovery_alias")]
+    pub alias: String,
+    /// Endpoint to list and query the models at, instead of the provider's
+    /// default or, for Ollama, `ollama.host`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+}
+
+fn default_discovery_alias() -> String {
+    "{model}".to_string()
+}
+
+/// A model alias whose requests are spread over several backends.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct BalancedModelConfig {
+    pub name: String,
+    #[serde(default)]
+    pub strategy: BalanceStrategy,
+    pub backends: Vec<BackendConfig>,
+    #[serde(default = "default_eject_after")]
+    pub eject_after: u32,
+    #[serde(default = "default_eject_for_secs")]
+    pub eject_for_secs: u64,
+}
+
+/// A virtual model answering with the first of `models` that succeeds.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FallbackModelConfig {
+    pub name: String,
+    pub models: Vec<String>,
+    #[serde(default = "default_fallback_on")]
+    pub fallback_on: Vec<ErrorClass>,
+}
+
+/// A virtual model handing each request to the model of the first rule the
+/// request meets, or to `default`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RouterModelConfig {
+    pub name: String,
+    #[serde(default)]
+    pub rules: Vec<RouteRuleConfig>,
+    pub default: String,
+    /// Model asked for the verdict that rules with `classifier` check.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classifier: Option<ClassifierConfig>,
+}
+
+/// The model for requests meeting every condition set here.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RouteRuleConfig {
+    pub model: String,
+    /// Bounds on the prompt's estimated token count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    /// Features the request must ask for in `Requires`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<Capability>,
+    /// Values of the `X-Caller` header the rule applies to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub callers: Vec<String>,
+    /// Answer the router's classifier must give, ignoring case.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classifier: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ClassifierConfig {
+    pub model: String,
+    /// Put before the prompt; should ask for a one-word answer.
+    #[serde(default = "default_classifier_instructions")]
+    pub instructions: String,
+    /// How much of the prompt the classifier sees.
+    #[serde(default = "default_classifier_max_prompt_chars")]
+    pub max_prompt_chars: usize,
+    /// Rules needing a verdict are skipped when none came in time.
+    #[serde(default = "default_classifier_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+/// Circuit breaker applied to every provider backend.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct CircuitBreakerConfig {
+    pub enabled: bool,
+    pub failure_rate: f64,
+    pub window: usize,
+    pub min_requests: usize,
+    pub open_for_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_rate: 0.5,
+            window: 20,
+            min_requests: 5,
+            open_for_secs: 30,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn settings(&self) -> Option<CircuitBreakerSettings> {
+        if !self.enabled {
+            return None;
+        }
+
+        Some(CircuitBreakerSettings {
+            failure_rate: self.failure_rate,
+            window: self.window.max(1),
+            min_requests: self.min_requests,
+            open_for: Duration::from_secs(self.open_for_secs),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    Text,
+    Json,
+    /// Plain lines with syslog priority prefixes, for stderr captured by
+    /// journald.
+    Journal,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Level or filter directive, e.g. `info` or `llm_api=debug,warn`.
+    pub level: String,
+    pub format: LogFormat,
+    /// File to append logs to instead of stderr.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+            file: None,
+        }
+    }
+}
+
+/// Export of request and provider call spans to an OpenTelemetry collector.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct TelemetryConfig {
+    pub enabled: bool,
+    /// OTLP/HTTP traces endpoint of the collector.
+    pub endpoint: String,
+    pub service_name: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "llmapi-rust".to_string(),
+        }
+    }
+}
+
+/// Upstream checks run by `GET /readyz`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct ReadinessConfig {
+    /// Check that each provider is reachable and accepts its credentials.
+    pub check_providers: bool,
+    /// How long a check result is reused before the provider is asked again.
+    pub cache_secs: u64,
+    pub timeout_secs: u64,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        Self {
+            check_providers: false,
+            cache_secs: 300,
+            timeout_secs: 5,
+        }
+    }
+}
+
+impl ReadinessConfig {
+    pub fn build(&self) -> Option<ReadinessChecker> {
+        self.check_providers.then(|| ReadinessChecker::new(
+            Duration::from_secs(self.cache_secs),
+            Duration::from_secs(self.timeout_secs),
+        ))
+    }
+}
+
+/// Keys of the built-in provider models. Each falls back to the provider's
+/// environment variable when not set.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeysConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub openai: Option<Secret>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anthropic: Option<Secret>,
+}
+
+/// Access to the `/admin` endpoints.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Token callers send as `Authorization: Bearer <token>`. The admin
+    /// endpoints are disabled without one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<Secret>,
+}
+
+/// How keys referenced with `file:` and `exec:` are read.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct SecretsConfig {
+    /// How long a key read from a file or command is used before reading it again.
+    pub refresh_secs: u64,
+    pub exec_timeout_secs: u64,
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        Self {
+            refresh_secs: 300,
+            exec_timeout_secs: 10,
+        }
+    }
+}
+
+impl SecretsConfig {
+    /// The configured key, or else the one in the environment variable
+    /// `env_var`.
+    pub fn api_key(&self, configured: Option<&Secret>, env_var: &str) -> Option<ApiKey> {
+        match configured {
+            Some(secret) => Some(self.key(secret)),
+            None => std::env::var(env_var).ok().map(ApiKey::from_value),
+        }
+    }
+
+    /// Reads `secret` as set up here.
+    pub fn key(&self, secret: &Secret) -> ApiKey {
+        ApiKey::new(
+            secret.clone(),
+            Duration::from_secs(self.refresh_secs),
+            Duration::from_secs(self.exec_timeout_secs),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheBackend {
+    Memory,
+    Disk,
+}
+
+/// Cache for responses to identical `/query` requests.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub backend: CacheBackend,
+    pub ttl_secs: u64,
+    /// Maximum number of entries kept. The disk backend may exceed it until
+    /// its next sweep.
+    pub max_entries: usize,
+    /// Directory of the disk backend, defaults to `cache` next to the config file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: CacheBackend::Memory,
+            ttl_secs: 3600,
+            max_entries: 1000,
+            directory: None,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn build(&self) -> Option<ResponseCache> {
+        if !self.enabled {
+            return None;
+        }
+
+        let ttl = Duration::from_secs(self.ttl_secs);
+        Some(match self.backend {
+            CacheBackend::Memory => ResponseCache::in_memory(self.max_entries, ttl),
+            CacheBackend::Disk => {
+                let directory = self.directory.clone().unwrap_or_else(|| Config::config_dir().join("cache"));
+                ResponseCache::on_disk(directory, self.max_entries, ttl)
+            }
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmbeddingProvider {
+    /// Hashing embedding computed in-process, see `HashingEmbeddingModel`.
+    Local,
+    #[serde(rename = "openai")]
+    OpenAi,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EmbeddingConfig {
+    pub provider: EmbeddingProvider,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<Secret>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Vector size of the local provider.
+    #[serde(default = "default_local_dimensions")]
+    pub dimensions: usize,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            provider: EmbeddingProvider::Local,
+            model: None,
+            api_key: None,
+            base_url: None,
+            dimensions: default_local_dimensions(),
+        }
+    }
+}
+
+impl EmbeddingConfig {
+    pub fn build(&self, secrets: &SecretsConfig) -> Option<Arc<dyn EmbeddingModel>> {
+        match self.provider {
+            EmbeddingProvider::Local => Some(Arc::new(HashingEmbeddingModel::new(self.dimensions))),
+            EmbeddingProvider::OpenAi => {
+                let Some(api_key) = secrets.api_key(self.api_key.as_ref(), "OPENAI_API_KEY") else {
+                    tracing::warn!("No API key for OpenAI embedding model, semantic cache disabled");
+                    return None;
+                };
+                let model = self.model.clone().unwrap_or_else(|| "text-embedding-3-small".to_string());
+                let mut embedder = OpenAiEmbeddingModel::new(api_key, model);
+                if let Some(base_url) = &self.base_url {
+                    embedder = embedder.with_base_url(base_url.clone());
+                }
+                Some(Arc::new(embedder))
+            }
+        }
+    }
+}
+
+/// Cache answering prompts similar in meaning to an earlier one.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct SemanticCacheConfig {
+    pub enabled: bool,
+    pub embedding: EmbeddingConfig,
+    /// Minimum cosine similarity, 0.0 to 1.0, for a cached answer to be used.
+    pub threshold: f32,
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    /// File the vector index is kept in, defaults to `semantic-cache.jsonl`
+    /// next to the config file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_path: Option<PathBuf>,
+}
+
+impl Default for SemanticCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            embedding: EmbeddingConfig::default(),
+            threshold: 0.95,
+            ttl_secs: 86400,
+            max_entries: 10000,
+            index_path: None,
+        }
+    }
+}
+
+impl SemanticCacheConfig {
+    pub fn build(&self, secrets: &SecretsConfig) -> Option<SemanticCache> {
+        if !self.enabled {
+            return None;
+        }
+
+        let index_path = self.index_path.clone().unwrap_or_else(|| Config::config_dir().join("semantic-cache.jsonl"));
+        Some(SemanticCache::new(
+            self.embedding.build(secrets)?,
+            self.threshold,
+            Duration::from_secs(self.ttl_secs),
+            self.max_entries,
+            Some(index_path),
+        ))
+    }
+}
+
+fn default_local_dimensions() -> usize {
+    256
+}
+
+pub const DEFAULT_OLLAMA_HOST: &str = "http://localhost:11434";
+
+fn default_ollama_host() -> String {
+    DEFAULT_OLLAMA_HOST.to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_eject_after() -> u32 {
+    3
+}
+
+fn default_eject_for_secs() -> u64 {
+    30
+}
+
+fn default_fallback_on() -> Vec<ErrorClass> {
+    vec![
+        ErrorClass::Overloaded,
+        ErrorClass::RateLimited,
+        ErrorClass::ServerError,
+        ErrorClass::Timeout,
+        ErrorClass::Network,
+        ErrorClass::CircuitOpen,
+    ]
+}
+
+fn default_classifier_instructions() -> String {
+    "Decide whether the request below is easy or hard for a language model. \
+     Short factual questions, small rewrites and simple formatting are easy. \
+     Requests needing reasoning, code, maths or long answers are hard. \
+     Answer with the single word easy or hard."
+        .to_string()
+}
+
+fn default_classifier_max_prompt_chars() -> usize {
+    2000
+}
+
+fn default_classifier_timeout_ms() -> u64 {
+    5000
 }
 
 impl Default for Config {
     fn default() -> Self {
-        Self { port: 3000 }
+        Self {
+            port: 3000,
+            bind_address: "0.0.0.0".to_string(),
+            unix_socket: None,
+            tls: None,
+            cors: CorsConfig::default(),
+            max_body_bytes: 2 * 1024 * 1024,
+            timeouts: TimeoutConfig::default(),
+            prompt_limits: PromptLimitsConfig::default(),
+            shutdown_timeout_secs: 30,
+            ollama: None,
+            balanced_models: Vec::new(),
+            fallback_models: Vec::new(),
+            router_models: Vec::new(),
+            discovery: DiscoveryConfig::default(),
+            aliases: BTreeMap::new(),
+            default_model: None,
+            model_info: BTreeMap::new(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            cache: CacheConfig::default(),
+            semantic_cache: SemanticCacheConfig::default(),
+            deduplicate_requests: true,
+            watch_config: true,
+            logging: LoggingConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            readiness: ReadinessConfig::default(),
+            api_keys: ApiKeysConfig::default(),
+            secrets: SecretsConfig::default(),
+            admin: AdminConfig::default(),
+        }
     }
 }
 
+/// Why the config file could not be used.
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: PathBuf, error: std::io::Error },
+    Parse { path: PathBuf, line: usize, column: usize, message: String, source_line: String },
+    Document { path: PathBuf, message: String },
+    Field { path: PathBuf, key: String, message: String },
+    Invalid { path: PathBuf, errors: Vec<String> },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read { path, error } => write!(f, "Failed to read {}: {}", path.display(), error),
+            ConfigError::Parse { path, line, column, message, source_line } => {
+                write!(f, "{}:{}:{}: {}", path.display(), line, column, message)?;
+                if !source_line.is_empty() {
+                    write!(f, "\n{:>5} | {}", line, source_line)?;
+                }
+                Ok(())
+            }
+            ConfigError::Document { path, message } => write!(f, "{}: {}", path.display(), message),
+            ConfigError::Field { path, key, message } => write!(f, "{}: {}: {}", path.display(), key, message),
+            ConfigError::Invalid { path, errors } => {
+                write!(f, "{} is invalid:", path.display())?;
+                for error in errors {
+                    write!(f, "\n  - {}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Config file chosen with `--config` or `LLMAPI_CONFIG`.
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Profile chosen with `--profile` or `LLMAPI_PROFILE`.
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// File names looked for in the config directory, in order.
+const DEFAULT_FILE_NAMES: [&str; 4] = ["config.json", "config.toml", "config.yaml", "config.yml"];
+
 impl Config {
-    pub fn load() -> Self {
+    /// Loads and validates the config file. A missing file yields the
+    /// defaults; an unreadable, malformed or invalid one is an error.
+    pub fn load() -> Result<Self, ConfigError> {
         let config_path = Self::config_path();
-        if config_path.exists() {
-            if let Ok(content) = fs::read_to_string(&config_path) {
-                if let Ok(config) = serde_json::from_str(&content) {
-                    return config;
+        if !config_path.exists() {
+            return match PROFILE.get() {
+                Some(profile) => Err(ConfigError::Document {
+                    path: config_path,
+                    message: format!("profile '{}' is not defined, the file does not exist", profile),
+                }),
+                None => Ok(Self::default()),
+            };
+        }
+
+        let content = fs::read_to_string(&config_path)
+            .map_err(|error| ConfigError::Read { path: config_path.clone(), error })?;
+        Self::from_content(&config_path, &content)
+    }
+
+    /// Builds the config from the content of the file at `path`: includes
+    /// are merged, the selected profile applied, variables interpolated and
+    /// the result validated.
+    pub fn from_content(path: &Path, content: &str) -> Result<Self, ConfigError> {
+        let (document, from_environment) = source::load_document(path, content, PROFILE.get().map(String::as_str))?;
+        let config: Self = serde_path_to_error::deserialize(document).map_err(|e| {
+            let key = e.path().to_string();
+            // Messages quote the offending value, which may be a secret
+            // when it came from the environment
+            let message = if from_environment.contains(&key) {
+                "invalid value taken from the environment".to_string()
+            } else {
+                redact_credentials(&e.into_inner().to_string())
+            };
+            ConfigError::Field { path: path.to_path_buf(), key, message }
+        })?;
+        config.validate()
+            .map_err(|errors| ConfigError::Invalid { path: path.to_path_buf(), errors })?;
+        Ok(config)
+    }
+
+    /// Checks settings that parse but cannot work, returning every problem
+    /// found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.port == 0 && self.unix_socket.is_none() {
+            errors.push("port must not be 0".to_string());
+        }
+        if self.bind_address.is_empty() {
+            errors.push("bind_address must not be empty".to_string());
+        }
+        if cfg!(not(unix)) && self.unix_socket.is_some() {
+            errors.push("unix_socket is only supported on Linux and macOS".to_string());
+        }
+        for origin in &self.cors.allowed_origins {
+            let valid = origin == "*"
+                || ((origin.starts_with("http://") || origin.starts_with("https://"))
+                    && !origin.ends_with('/')
+                    && origin.parse::<axum::http::HeaderValue>().is_ok());
+            if !valid {
+                errors.push(format!("cors.allowed_origins: '{}' is not `*` or an origin like https://example.com", origin));
+            }
+        }
+        if self.max_body_bytes == 0 {
+            errors.push("max_body_bytes must not be 0".to_string());
+        }
+        if self.timeouts.request_ms == 0 {
+            errors.push("timeouts.request_ms must not be 0".to_string());
+        }
+        if self.timeouts.max_ms == 0 {
+            errors.push("timeouts.max_ms must not be 0".to_string());
+        }
+        for (model, timeout_ms) in &self.timeouts.models {
+            if *timeout_ms == 0 {
+                errors.push(format!("timeouts.models '{}' must not be 0", model));
+            }
+        }
+        if let Some(tls) = &self.tls {
+            for (key, path) in [("tls.cert", &tls.cert), ("tls.key", &tls.key)] {
+                if !path.is_file() {
+                    errors.push(format!("{}: {} does not exist", key, path.display()));
+                }
+            }
+        }
+
+        let mut known_models: Vec<&str> = BUILTIN_MODELS.to_vec();
+        if let Some(ollama) = &self.ollama {
+            known_models.extend(ollama.models.iter().map(String::as_str));
+        }
+
+        for balanced in &self.balanced_models {
+            if balanced.name.is_empty() {
+                errors.push("balanced_models: name must not be empty".to_string());
+            }
+            if balanced.backends.is_empty() {
+                errors.push(format!("balanced_models '{}': no backends", balanced.name));
+            }
+            if balanced.strategy == BalanceStrategy::Weighted && balanced.backends.iter().all(|backend| backend.weight == 0) {
+                errors.push(format!("balanced_models '{}': all backend weights are 0", balanced.name));
+            }
+            known_models.push(&balanced.name);
+        }
+
+        for (provider, rules) in self.discovery.providers() {
+            let key = format!("discovery.{}", provider);
+            if rules.include.is_empty() {
+                errors.push(format!("{}.include must list at least one model or pattern", key));
+            }
+            if rules.include.iter().chain(&rules.exclude).any(String::is_empty) {
+                errors.push(format!("{}: patterns must not be empty", key));
+            }
+            if !rules.alias.contains("{model}") {
+                errors.push(format!("{}.alias must contain {{model}}", key));
+            }
+        }
+
+        // Fallback chains may refer to chains defined before them. Discovered
+        // models are only known once the providers have been asked.
+        for fallback in &self.fallback_models {
+            if fallback.name.is_empty() {
+                errors.push("fallback_models: name must not be empty".to_string());
+            }
+            if fallback.models.is_empty() {
+                errors.push(format!("fallback_models '{}': no models", fallback.name));
+            }
+            for name in &fallback.models {
+                if !known_models.contains(&name.as_str()) && !self.discovery.enabled() {
+                    errors.push(format!("fallback_models '{}': unknown model '{}'", fallback.name, name));
+                }
+            }
+            known_models.push(&fallback.name);
+        }
+
+        // Routers may refer to any model defined before them, routers too
+        for router in &self.router_models {
+            let key = format!("router_models '{}'", router.name);
+            if router.name.is_empty() {
+                errors.push("router_models: name must not be empty".to_string());
+            }
+            let unknown = |name: &String| !known_models.contains(&name.as_str()) && !self.discovery.enabled();
+            if unknown(&router.default) {
+                errors.push(format!("{}: unknown default model '{}'", key, router.default));
+            }
+            for (index, rule) in router.rules.iter().enumerate() {
+                let rule_key = format!("{}: rule {}", key, index + 1);
+                if unknown(&rule.model) {
+                    errors.push(format!("{}: unknown model '{}'", rule_key, rule.model));
+                }
+                if let (Some(min), Some(max)) = (rule.min_tokens, rule.max_tokens) {
+                    if min > max {
+                        errors.push(format!("{}: min_tokens is greater than max_tokens", rule_key));
+                    }
+                }
+                if rule.classifier.is_some() && router.classifier.is_none() {
+                    errors.push(format!("{}: checks the classifier's verdict, but no classifier is configured", rule_key));
+                }
+            }
+            if let Some(classifier) = &router.classifier {
+                if unknown(&classifier.model) {
+                    errors.push(format!("{}: unknown classifier model '{}'", key, classifier.model));
+                }
+                if classifier.max_prompt_chars == 0 {
+                    errors.push(format!("{}: classifier.max_prompt_chars must not be 0", key));
+                }
+                if classifier.timeout_ms == 0 {
+                    errors.push(format!("{}: classifier.timeout_ms must not be 0", key));
+                }
+            }
+            known_models.push(&router.name);
+        }
+
+        // Aliases must be told apart from model names and each other even
+        // when looked up in other case
+        let mut taken: Vec<String> = known_models.iter().map(|name| name.to_lowercase()).collect();
+        for (name, aliases) in &self.aliases {
+            if !known_models.contains(&name.as_str()) && !self.discovery.enabled() {
+                errors.push(format!("aliases: unknown model '{}'", name));
+            }
+            for alias in aliases {
+                if alias.is_empty() {
+                    errors.push(format!("aliases '{}': alias must not be empty", name));
+                } else if taken.contains(&alias.to_lowercase()) {
+                    errors.push(format!("aliases '{}': '{}' is already a model name or alias", name, alias));
+                } else {
+                    taken.push(alias.to_lowercase());
+                }
+            }
+        }
+        if let Some(default_model) = &self.default_model {
+            let known = known_models.contains(&default_model.as_str())
+                || self.aliases.values().flatten().any(|alias| alias == default_model);
+            if !known && !self.discovery.enabled() {
+                errors.push(format!("default_model: unknown model '{}'", default_model));
+            }
+        }
+
+        for (name, capabilities) in &self.model_info {
+            errors.extend(capabilities.validate(&format!("model_info '{}'", name)));
+        }
+
+        let breaker = &self.circuit_breaker;
+        if !(breaker.failure_rate > 0.0 && breaker.failure_rate <= 1.0) {
+            errors.push("circuit_breaker.failure_rate must be greater than 0 and at most 1".to_string());
+        }
+        if breaker.window == 0 {
+            errors.push("circuit_breaker.window must not be 0".to_string());
+        }
+
+        if self.cache.max_entries == 0 {
+            errors.push("cache.max_entries must not be 0".to_string());
+        }
+
+        let semantic = &self.semantic_cache;
+        if !(semantic.threshold > 0.0 && semantic.threshold <= 1.0) {
+            errors.push("semantic_cache.threshold must be greater than 0 and at most 1".to_string());
+        }
+        if semantic.embedding.dimensions == 0 {
+            errors.push("semantic_cache.embedding.dimensions must not be 0".to_string());
+        }
+
+        let mut secrets: Vec<(String, &Secret)> = Vec::new();
+        secrets.extend(self.api_keys.openai.iter().map(|secret| ("api_keys.openai".to_string(), secret)));
+        secrets.extend(self.api_keys.anthropic.iter().map(|secret| ("api_keys.anthropic".to_string(), secret)));
+        for balanced in &self.balanced_models {
+            for backend in &balanced.backends {
+                if let Some(secret) = &backend.api_key {
+                    secrets.push((format!("balanced_models '{}': api_key of backend '{}'", balanced.name, backend.model), secret));
                 }
             }
         }
-        Self::default()
+        if let Some(secret) = &semantic.embedding.api_key {
+            secrets.push(("semantic_cache.embedding.api_key".to_string(), secret));
+        }
+        if let Some(secret) = &self.admin.token {
+            secrets.push(("admin.token".to_string(), secret));
+        }
+        for (key, secret) in secrets {
+            if let Err(e) = secret.validate() {
+                errors.push(format!("{}: {}", key, e));
+            }
+        }
+        if self.secrets.exec_timeout_secs == 0 {
+            errors.push("secrets.exec_timeout_secs must not be 0".to_string());
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
     }
 
     pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
         let config_path = Self::config_path();
-        
+
         // Ensure the directory exists
         if let Some(parent) = config_path.parent() {
             fs::create_dir_all(parent)?;
         }
-        
-        let content = serde_json::to_string_pretty(self)?;
+
+        let content = source::serialize_document(&config_path, &serde_json::to_value(self)?)?;
         fs::write(config_path, content)?;
         Ok(())
     }
 
-    fn config_path() -> PathBuf {
+    /// Uses `path` instead of the default config file for the rest of the
+    /// process. Only the first call has an effect.
+    pub fn use_path(path: PathBuf) {
+        let _ = CONFIG_PATH.set(path);
+    }
+
+    /// Applies the named profile over the base settings for the rest of the
+    /// process. Only the first call has an effect.
+    pub fn use_profile(profile: String) {
+        let _ = PROFILE.set(profile);
+    }
+
+    /// The profile chosen with `--profile`, if any.
+    pub fn profile() -> Option<&'static str> {
+        PROFILE.get().map(String::as_str)
+    }
+
+    /// The config file: the one chosen with `--config`, otherwise the first
+    /// of `config.json`, `config.toml`, `config.yaml` and `config.yml` that
+    /// exists in the config directory.
+    pub fn config_path() -> PathBuf {
+        if let Some(path) = CONFIG_PATH.get() {
+            return path.clone();
+        }
+
+        let directory = Self::default_dir();
+        DEFAULT_FILE_NAMES
+            .iter()
+            .map(|name| directory.join(name))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| directory.join(DEFAULT_FILE_NAMES[0]))
+    }
+
+    /// Directory of the config file, which also holds the default cache
+    /// locations.
+    pub fn config_dir() -> PathBuf {
+        Self::config_path()
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    fn default_dir() -> PathBuf {
         let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
         path.push("llmapi-rust");
-        path.push("config.json");
         path
     }
-} 
\ No newline at end of file
+}
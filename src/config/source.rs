@@ -0,0 +1,356 @@
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use super::ConfigError;
+use crate::secret::redact_credentials;
+
+/// Config file formats, told apart by the file extension. Anything that is
+/// not TOML or YAML is read as JSON.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("yaml") | Some("yml") => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
+}
+
+/// Parses a config file into a document, pointing syntax errors at their
+/// line.
+pub fn parse_document(path: &Path, content: &str) -> Result<Value, ConfigError> {
+    match Format::of(path) {
+        Format::Json => serde_json::from_str(content).map_err(|e| {
+            parse_error(path, content, e.line(), e.column(), strip_location(e.to_string()))
+        }),
+        Format::Toml => toml::from_str(content).map_err(|e| {
+            let (line, column) = e.span()
+                .map(|span| line_and_column(content, span.start))
+                .unwrap_or((0, 0));
+            parse_error(path, content, line, column, e.message().to_string())
+        }),
+        Format::Yaml => serde_yaml::from_str(content).map_err(|e| {
+            let (line, column) = e.location()
+                .map(|location| (location.line(), location.column()))
+                .unwrap_or((0, 0));
+            parse_error(path, content, line, column, strip_location(e.to_string()))
+        }),
+    }
+}
+
+/// Writes a document in the format of `path`. TOML has no null, so unset
+/// values are left out there.
+pub fn serialize_document(path: &Path, document: &Value) -> Result<String, String> {
+    match Format::of(path) {
+        Format::Json => serde_json::to_string_pretty(document).map_err(|e| e.to_string()),
+        Format::Toml => toml::to_string_pretty(&without_nulls(document.clone())).map_err(|e| e.to_string()),
+        Format::Yaml => serde_yaml::to_string(document).map_err(|e| e.to_string()),
+    }
+}
+
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(without_nulls).collect()),
+        value => value,
+    }
+}
+
+/// Reads `path` with its includes merged in, applies `profile` when given
+/// and interpolates environment variables. Returns the document and the
+/// keys whose values came from the environment.
+pub fn load_document(path: &Path, content: &str, profile: Option<&str>) -> Result<(Value, Vec<String>), ConfigError> {
+    let mut document = resolve(path, content, &mut Vec::new())?;
+
+    let profiles = match &mut document {
+        Value::Object(map) => map.remove("profiles"),
+        _ => None,
+    };
+    if let Some(profile) = profile {
+        let overlay = profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(profile))
+            .cloned()
+            .ok_or_else(|| ConfigError::Document {
+                path: path.to_path_buf(),
+                message: format!("profile '{}' is not defined", profile),
+            })?;
+        merge(&mut document, overlay);
+    }
+
+    let replaced = interpolate(path, &mut document)?;
+    Ok((document, replaced))
+}
+
+/// Parses one file and merges it over the files it includes. `stack` holds
+/// the files being resolved, to catch include cycles.
+fn resolve(path: &Path, content: &str, stack: &mut Vec<PathBuf>) -> Result<Value, ConfigError> {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(ConfigError::Document {
+            path: path.to_path_buf(),
+            message: "includes itself".to_string(),
+        });
+    }
+
+    let mut document = parse_document(path, content)?;
+
+    let mut includes = match &mut document {
+        Value::Object(map) => map.remove("include"),
+        _ => None,
+    };
+    if let Some(includes) = &mut includes {
+        interpolate_value(includes, "include".to_string(), &mut Vec::new())
+            .map_err(|(key, message)| ConfigError::Field { path: path.to_path_buf(), key, message })?;
+    }
+    let includes = match includes {
+        None => Vec::new(),
+        Some(Value::String(include)) => vec![include],
+        Some(Value::Array(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                Value::String(include) => Ok(include),
+                _ => Err(()),
+            })
+            .collect::<Result<_, _>>()
+            .map_err(|_| include_error(path))?,
+        Some(_) => return Err(include_error(path)),
+    };
+
+    stack.push(canonical);
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let mut merged = Value::Object(Map::new());
+    for include in includes {
+        let include_path = directory.join(include);
+        let include_content = fs::read_to_string(&include_path)
+            .map_err(|error| ConfigError::Read { path: include_path.clone(), error })?;
+        merge(&mut merged, resolve(&include_path, &include_content, stack)?);
+    }
+    stack.pop();
+
+    merge(&mut merged, document);
+    Ok(merged)
+}
+
+fn include_error(path: &Path) -> ConfigError {
+    ConfigError::Document {
+        path: path.to_path_buf(),
+        message: "include must be a file name or a list of file names".to_string(),
+    }
+}
+
+/// Merges `overlay` into `base`. Objects are merged key by key, everything
+/// else, including lists, is replaced.
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Replaces `${NAME}` in the string values of `document` with the
+/// environment variable `NAME`, and `${NAME:-default}` with `default` when
+/// the variable is unset or empty. `$${` stands for a literal `${`. A value
+/// that is nothing but one variable becomes a number or boolean when the
+/// variable holds one. Returns the keys whose values were replaced.
+///
+/// Values are replaced after parsing, so they cannot break the file's
+/// syntax, and are never part of an error message.
+pub fn interpolate(path: &Path, document: &mut Value) -> Result<Vec<String>, ConfigError> {
+    let mut replaced = Vec::new();
+    interpolate_value(document, String::new(), &mut replaced)
+        .map_err(|(key, message)| ConfigError::Field { path: path.to_path_buf(), key, message })?;
+    Ok(replaced)
+}
+
+fn interpolate_value(value: &mut Value, key: String, replaced: &mut Vec<String>) -> Result<(), (String, String)> {
+    match value {
+        Value::Object(map) => {
+            for (name, value) in map.iter_mut() {
+                let key = if key.is_empty() { name.clone() } else { format!("{}.{}", key, name) };
+                interpolate_value(value, key, replaced)?;
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                interpolate_value(item, format!("{}[{}]", key, index), replaced)?;
+            }
+        }
+        Value::String(text) if text.contains("${") => {
+            let whole = is_one_variable(text);
+            let result = interpolate_text(text).map_err(|message| (key.clone(), message))?;
+            *value = match whole.then(|| typed(&result)).flatten() {
+                Some(typed) => typed,
+                None => Value::String(result),
+            };
+            replaced.push(key);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_text(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            result.push_str(&rest[..start - 1]);
+            result.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        result.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| "unterminated ${".to_string())?;
+
+        let expression = &rest[start + 2..start + end];
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expression, None),
+        };
+        match (std::env::var(name).ok(), default) {
+            (Some(value), Some(default)) if value.is_empty() => result.push_str(default),
+            (Some(value), _) => result.push_str(&value),
+            (None, Some(default)) => result.push_str(default),
+            (None, None) => return Err(format!("environment variable {} is not set", name)),
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Whether `text` is exactly one `${...}` expression.
+fn is_one_variable(text: &str) -> bool {
+    text.strip_prefix("${")
+        .and_then(|rest| rest.strip_suffix('}'))
+        .is_some_and(|expression| !expression.contains('}'))
+}
+
+/// `text` as a number or boolean, if it is one.
+fn typed(text: &str) -> Option<Value> {
+    match text {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => serde_json::from_str::<serde_json::Number>(text).ok().map(Value::Number),
+    }
+}
+
+fn parse_error(path: &Path, content: &str, line: usize, column: usize, message: String) -> ConfigError {
+    ConfigError::Parse {
+        path: path.to_path_buf(),
+        line,
+        column,
+        message,
+        source_line: redact_credentials(content.lines().nth(line.saturating_sub(1)).unwrap_or("")),
+    }
+}
+
+/// Drops the " at line X column Y" serde_json and serde_yaml append, which
+/// is shown separately.
+fn strip_location(message: String) -> String {
+    message
+        .rsplit_once(" at line ")
+        .map(|(message, _)| message.to_string())
+        .unwrap_or(message)
+}
+
+/// 1-based line and column of a byte offset.
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|index| index + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file: &str, content: &str) -> Result<(Value, Vec<String>), ConfigError> {
+        load_document(Path::new(file), content, None)
+    }
+
+    #[test]
+    fn interpolates_string_values() {
+        std::env::set_var("LLMAPI_TEST_LEVEL", "debug");
+        std::env::set_var("LLMAPI_TEST_EMPTY", "");
+        let (document, replaced) = load(
+            "config.toml",
+            "[logging]\nlevel = \"${LLMAPI_TEST_LEVEL}\"\nformat = \"${LLMAPI_TEST_EMPTY:-json}\"\nfile = \"/var/log/${LLMAPI_TEST_UNSET:-llm}.log\"\n",
+        ).unwrap();
+
+        assert_eq!(document["logging"]["level"], "debug");
+        assert_eq!(document["logging"]["format"], "json");
+        assert_eq!(document["logging"]["file"], "/var/log/llm.log");
+        assert_eq!(replaced, ["logging.level", "logging.format", "logging.file"]);
+    }
+
+    #[test]
+    fn escapes_literal_dollar_brace() {
+        let (document, _) = load("config.json", r#"{"model": "$${NOT_A_VARIABLE}", "other": "a$$b"}"#).unwrap();
+        assert_eq!(document["model"], "${NOT_A_VARIABLE}");
+        assert_eq!(document["other"], "a$$b");
+    }
+
+    #[test]
+    fn unset_variable_names_key_not_value() {
+        let error = load("config.yaml", "cache:\n  path: \"${LLMAPI_TEST_UNSET}\"\n").unwrap_err().to_string();
+        assert_eq!(error, "config.yaml: cache.path: environment variable LLMAPI_TEST_UNSET is not set");
+
+        let error = load("config.json", r#"{"models": ["a", "${LLMAPI_TEST_UNSET"]}"#).unwrap_err().to_string();
+        assert_eq!(error, "config.json: models[1]: unterminated ${");
+    }
+
+    #[test]
+    fn ignores_comments_and_unselected_profiles() {
+        let content = "# port = \"${LLMAPI_TEST_UNSET}\"\nport = 8080\n[profiles.other]\nport = \"${LLMAPI_TEST_UNSET}\"\n";
+        let (document, replaced) = load("config.toml", content).unwrap();
+        assert_eq!(document["port"], 8080);
+        assert!(replaced.is_empty());
+    }
+
+    #[test]
+    fn values_cannot_break_syntax() {
+        std::env::set_var("LLMAPI_TEST_TRICKY", "a\"b\nc");
+        let (document, _) = load("config.json", r#"{"api_key": "${LLMAPI_TEST_TRICKY}"}"#).unwrap();
+        assert_eq!(document["api_key"], "a\"b\nc");
+    }
+
+    #[test]
+    fn whole_value_variables_are_typed() {
+        std::env::set_var("LLMAPI_TEST_PORT", "9090");
+        std::env::set_var("LLMAPI_TEST_FLAG", "true");
+        let (document, _) = load(
+            "config.yaml",
+            "port: \"${LLMAPI_TEST_PORT}\"\nenabled: \"${LLMAPI_TEST_FLAG}\"\nname: \"v${LLMAPI_TEST_PORT}\"\n",
+        ).unwrap();
+        assert_eq!(document["port"], 9090);
+        assert_eq!(document["enabled"], true);
+        assert_eq!(document["name"], "v9090");
+    }
+}
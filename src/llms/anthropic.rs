@@ -1,13 +1,23 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use crate::llms::LlmModel;
+use crate::llms::{Completion, LlmModel, QueryError, TokenUsage};
+use crate::llms::capabilities::ModelCapabilities;
+use crate::llms::error::{ErrorClass, ProviderError};
 use tokio::time;
+use crate::metrics::metrics;
+use crate::secret::ApiKey;
+use crate::telemetry;
+
+const DEFAULT_BASE_URL: &str = "https://api.anthropic.com/v1";
 
 pub struct AnthropicModel {
     client: Client,
-    api_key: String,
+    api_key: ApiKey,
     model: String,
+    base_url: String,
+    /// Name the model is configured under, for metric labels
+    name: String,
 }
 
 #[derive(Serialize)]
@@ -28,17 +38,86 @@ struct Content {
     text: String,
 }
 
+#[derive(Deserialize)]
+struct AnthropicUsage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
+
 #[derive(Deserialize)]
 struct AnthropicResponse {
     content: Vec<Content>,
+    usage: Option<AnthropicUsage>,
+    stop_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicModelList {
+    data: Vec<AnthropicModelEntry>,
+    #[serde(default)]
+    has_more: bool,
+    last_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicModelEntry {
+    id: String,
 }
 
 impl AnthropicModel {
-    pub fn new(api_key: String, model: String) -> Self {
+    pub fn new(api_key: ApiKey, model: String) -> Self {
         Self {
             client: Client::new(),
             api_key,
+            name: model.clone(),
             model,
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
+    }
+
+    /// Labels the model's metrics with `name` instead of the model id.
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Ids of the models the API key has access to, following the pages of
+    /// the list.
+    pub async fn available_models(&self) -> Result<Vec<String>, QueryError> {
+        let api_key = self.api_key.value().await?;
+        let mut models = Vec::new();
+        let mut after: Option<String> = None;
+
+        loop {
+            let mut request_builder = self.client
+                .get(format!("{}/models", self.base_url))
+                .query(&[("limit", "1000")])
+                .header("x-api-key", &api_key)
+                .header("anthropic-version", "2023-06-01");
+            if let Some(after) = &after {
+                request_builder = request_builder.query(&[("after_id", after)]);
+            }
+
+            let response = request_builder.send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(ProviderError::new(
+                    ErrorClass::from_status(status.as_u16()),
+                    format!("Anthropic API returned {}", status),
+                ).into());
+            }
+
+            let page: AnthropicModelList = response.json().await?;
+            models.extend(page.data.into_iter().map(|model| model.id));
+            match page.last_id {
+                Some(last_id) if page.has_more => after = Some(last_id),
+                _ => return Ok(models),
+            }
         }
     }
 }
@@ -53,7 +132,32 @@ impl LlmModel for AnthropicModel {
         "Anthropic"
     }
 
-    async fn query(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::known(&self.model)
+    }
+
+    async fn check(&self) -> Option<Result<(), QueryError>> {
+        Some(self.available_models().await.map(|_| ()))
+    }
+
+    #[tracing::instrument(
+        name = "provider_call",
+        skip_all,
+        fields(
+            provider = "Anthropic",
+            model = %self.model,
+            otel.name = %format!("chat {}", self.model),
+            otel.kind = "client",
+            gen_ai.operation.name = "chat",
+            gen_ai.system = "anthropic",
+            gen_ai.request.model = %self.model,
+            gen_ai.response.model = tracing::field::Empty,
+            gen_ai.usage.input_tokens = tracing::field::Empty,
+            gen_ai.usage.output_tokens = tracing::field::Empty,
+            gen_ai.response.finish_reasons = tracing::field::Empty,
+        ),
+    )]
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
         let request = AnthropicRequest {
             model: self.model.clone(),
             messages: vec![Message {
@@ -63,9 +167,10 @@ impl LlmModel for AnthropicModel {
             max_tokens: 1024,
         };
 
+        let api_key = self.api_key.value().await?;
         let mut response = self.client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.api_key)
+            .post(format!("{}/messages", self.base_url))
+            .header("x-api-key", &api_key)
             .header("anthropic-version", "2023-06-01")
             .header("Content-Type", "application/json")
             .json(&request)
@@ -81,12 +186,13 @@ impl LlmModel for AnthropicModel {
             time::sleep(time::Duration::from_secs(2)).await;
             
             // Log retry attempt
-            eprintln!("Anthropic API returned 529, retrying (attempt {}/{})...", attempts, MAX_ATTEMPTS-1);
+            tracing::warn!(attempt = attempts, max_attempts = MAX_ATTEMPTS - 1, "Anthropic API returned 529, retrying");
+            metrics().retries.with_label_values(&[self.name.as_str(), self.provider(), "provider"]).inc();
             
             // Retry the request
             response = self.client
-                .post("https://api.anthropic.com/v1/messages")
-                .header("x-api-key", &self.api_key)
+                .post(format!("{}/messages", self.base_url))
+                .header("x-api-key", &api_key)
                 .header("anthropic-version", "2023-06-01")
                 .header("Content-Type", "application/json")
                 .json(&request)
@@ -98,7 +204,10 @@ impl LlmModel for AnthropicModel {
 
         // If we still have a 529 after all attempts, make the error more descriptive
         if response.status() == 529 {
-            return Err(format!("Anthropic API returned 529 status code after {} attempts. Service is likely overloaded.", MAX_ATTEMPTS).into());
+            return Err(ProviderError::new(
+                ErrorClass::Overloaded,
+                format!("Anthropic API returned 529 status code after {} attempts. Service is likely overloaded.", MAX_ATTEMPTS),
+            ).into());
         }
 
         // Check for other errors
@@ -107,10 +216,23 @@ impl LlmModel for AnthropicModel {
 
         let response_data: AnthropicResponse = response.json().await?;
         
-        Ok(response_data
+        let text = response_data
             .content
             .first()
             .map(|content| content.text.clone())
-            .unwrap_or_else(|| String::from("No response generated.")))
+            .unwrap_or_else(|| String::from("No response generated."));
+
+        let completion = Completion {
+            text,
+            model: self.model.clone(),
+            provider: self.provider().to_string(),
+            usage: response_data.usage.map(|usage| TokenUsage {
+                prompt_tokens: usage.input_tokens,
+                completion_tokens: usage.output_tokens,
+            }),
+            finish_reason: response_data.stop_reason,
+        };
+        telemetry::record_completion(&completion);
+        Ok(completion)
     }
 } 
\ No newline at end of file
@@ -0,0 +1,232 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::llms::{Completion, LlmModel, ProviderHealth, QueryError};
+use crate::llms::capabilities::ModelCapabilities;
+use crate::llms::tokenizer::Tokenizer;
+use crate::llms::error::ErrorClass;
+use crate::metrics::metrics;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalanceStrategy {
+    #[default]
+    RoundRobin,
+    LeastInflight,
+    Weighted,
+}
+
+struct Backend {
+    model: Arc<dyn LlmModel>,
+    weight: u32,
+    inflight: AtomicUsize,
+    consecutive_failures: AtomicU32,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+impl Backend {
+    fn is_available(&self, now: Instant) -> bool {
+        match *self.ejected_until.lock().unwrap() {
+            Some(until) => now >= until,
+            None => true,
+        }
+    }
+}
+
+/// Decrements a backend's in-flight counter when the request finishes,
+/// including when the request future is dropped early.
+struct InflightGuard<'a>(&'a AtomicUsize);
+
+impl Drop for InflightGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A virtual model spreading requests over several backends serving the same
+/// model, e.g. different API keys or deployments. A backend failing with
+/// rate limit or server errors `eject_after` times in a row is skipped for
+/// `eject_for` and the request is retried on another backend.
+pub struct BalancedModel {
+    /// Name the model is configured under, for metric labels
+    name: String,
+    model_name: String,
+    strategy: BalanceStrategy,
+    backends: Vec<Backend>,
+    next: AtomicUsize,
+    eject_after: u32,
+    eject_for: Duration,
+}
+
+impl BalancedModel {
+    pub fn new(
+        name: String,
+        model_name: String,
+        strategy: BalanceStrategy,
+        backends: Vec<(Arc<dyn LlmModel>, u32)>,
+        eject_after: u32,
+        eject_for: Duration,
+    ) -> Self {
+        let backends = backends
+            .into_iter()
+            .map(|(model, weight)| Backend {
+                model,
+                weight: weight.max(1),
+                inflight: AtomicUsize::new(0),
+                consecutive_failures: AtomicU32::new(0),
+                ejected_until: Mutex::new(None),
+            })
+            .collect();
+
+        Self {
+            name,
+            model_name,
+            strategy,
+            backends,
+            next: AtomicUsize::new(0),
+            eject_after: eject_after.max(1),
+            eject_for,
+        }
+    }
+
+    /// Picks the next backend among those not yet tried for this request.
+    /// Ejected backends are only used when every remaining backend is ejected.
+    fn select(&self, tried: &[usize]) -> Option<usize> {
+        let now = Instant::now();
+        let remaining: Vec<usize> = (0..self.backends.len())
+            .filter(|index| !tried.contains(index))
+            .collect();
+        let available: Vec<usize> = remaining
+            .iter()
+            .copied()
+            .filter(|&index| self.backends[index].is_available(now))
+            .collect();
+        let candidates = if available.is_empty() { remaining } else { available };
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let counter = self.next.fetch_add(1, Ordering::Relaxed);
+        let index = match self.strategy {
+            BalanceStrategy::RoundRobin => candidates[counter % candidates.len()],
+            BalanceStrategy::LeastInflight => *candidates
+                .iter()
+                .min_by_key(|&&index| self.backends[index].inflight.load(Ordering::SeqCst))
+                .unwrap(),
+            BalanceStrategy::Weighted => {
+                let total: u32 = candidates.iter().map(|&index| self.backends[index].weight).sum();
+                let mut point = (counter as u32) % total;
+                *candidates
+                    .iter()
+                    .find(|&&index| {
+                        let weight = self.backends[index].weight;
+                        if point < weight {
+                            true
+                        } else {
+                            point -= weight;
+                            false
+                        }
+                    })
+                    .unwrap()
+            }
+        };
+
+        Some(index)
+    }
+
+    fn record_success(&self, backend: &Backend) {
+        backend.consecutive_failures.store(0, Ordering::SeqCst);
+        *backend.ejected_until.lock().unwrap() = None;
+    }
+
+    fn record_failure(&self, backend: &Backend) {
+        let failures = backend.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= self.eject_after {
+            tracing::warn!(
+                provider = backend.model.provider(),
+                model = %self.model_name,
+                eject_for_secs = self.eject_for.as_secs(),
+                failures,
+                "Ejecting backend after consecutive failures"
+            );
+            backend.consecutive_failures.store(0, Ordering::SeqCst);
+            *backend.ejected_until.lock().unwrap() = Some(Instant::now() + self.eject_for);
+        }
+    }
+}
+
+#[async_trait]
+impl LlmModel for BalancedModel {
+    fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    fn provider(&self) -> &str {
+        "Balanced"
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::common(self.backends.iter().map(|backend| backend.model.capabilities()))
+    }
+
+    fn tokenizer(&self) -> Tokenizer {
+        Tokenizer::common(self.backends.iter().map(|backend| backend.model.tokenizer()))
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.backends.iter().flat_map(|backend| backend.model.health()).collect()
+    }
+
+    /// Passes when any backend passes, since one working backend is enough
+    /// to answer requests.
+    async fn check(&self) -> Option<Result<(), QueryError>> {
+        let mut result = None;
+        for backend in &self.backends {
+            match backend.model.check().await {
+                Some(Ok(())) => return Some(Ok(())),
+                Some(Err(e)) => result = Some(Err(e)),
+                None => {}
+            }
+        }
+        result
+    }
+
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
+        let mut tried = Vec::new();
+        let mut last_error = None;
+
+        while let Some(index) = self.select(&tried) {
+            if !tried.is_empty() {
+                metrics().retries.with_label_values(&[self.name.as_str(), self.provider(), "backend"]).inc();
+            }
+            tried.push(index);
+            let backend = &self.backends[index];
+
+            backend.inflight.fetch_add(1, Ordering::SeqCst);
+            let result = {
+                let _guard = InflightGuard(&backend.inflight);
+                backend.model.query(prompt).await
+            };
+
+            match result {
+                Ok(completion) => {
+                    self.record_success(backend);
+                    return Ok(completion);
+                }
+                Err(e) => match ErrorClass::of(e.as_ref()) {
+                    ErrorClass::RateLimited | ErrorClass::Overloaded | ErrorClass::ServerError => {
+                        self.record_failure(backend);
+                        last_error = Some(e);
+                    }
+                    ErrorClass::CircuitOpen => last_error = Some(e),
+                    _ => return Err(e),
+                },
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| format!("Model '{}' has no backends", self.model_name).into()))
+    }
+}
@@ -0,0 +1,198 @@
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::llms::Completion;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CacheEntry {
+    created_at: u64,
+    completion: Completion,
+}
+
+enum Backend {
+    Memory(Mutex<LruCache<String, CacheEntry>>),
+    Disk { directory: PathBuf, max_entries: usize },
+}
+
+/// How often the disk backend removes expired entries and those beyond its
+/// size limit.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Stores successful completions so identical requests are answered without
+/// calling the provider again. Entries older than the TTL are ignored.
+pub struct ResponseCache {
+    backend: Backend,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    pub fn in_memory(max_entries: usize, ttl: Duration) -> Self {
+        let capacity = NonZeroUsize::new(max_entries).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            backend: Backend::Memory(Mutex::new(LruCache::new(capacity))),
+            ttl,
+        }
+    }
+
+    /// A cache writing one file per entry to `directory`. Call `sweep`
+    /// regularly to bound its size.
+    pub fn on_disk(directory: PathBuf, max_entries: usize, ttl: Duration) -> Self {
+        Self {
+            backend: Backend::Disk { directory, max_entries },
+            ttl,
+        }
+    }
+
+    /// Builds the cache key from the model alias, the upstream model and the
+    /// prompt with line endings and surrounding whitespace normalized.
+    pub fn key(model_alias: &str, model_name: &str, prompt: &str) -> String {
+        let prompt = prompt.replace("\r\n", "\n");
+        let mut hasher = Sha256::new();
+        for part in [model_alias, model_name, prompt.trim()] {
+            hasher.update(part.as_bytes());
+            hasher.update([0u8]);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Completion> {
+        let entry = match &self.backend {
+            Backend::Memory(cache) => cache.lock().unwrap().get(key).cloned()?,
+            Backend::Disk { directory, .. } => {
+                let content = tokio::fs::read_to_string(directory.join(format!("{}.json", key))).await.ok()?;
+                serde_json::from_str::<CacheEntry>(&content).ok()?
+            }
+        };
+
+        if now_secs().saturating_sub(entry.created_at) > self.ttl.as_secs() {
+            self.remove(key).await;
+            return None;
+        }
+
+        Some(entry.completion)
+    }
+
+    pub async fn put(&self, key: &str, completion: &Completion) {
+        let entry = CacheEntry {
+            created_at: now_secs(),
+            completion: completion.clone(),
+        };
+
+        match &self.backend {
+            Backend::Memory(cache) => {
+                cache.lock().unwrap().put(key.to_string(), entry);
+            }
+            Backend::Disk { directory, .. } => {
+                let result = async {
+                    tokio::fs::create_dir_all(directory).await?;
+                    let content = serde_json::to_string(&entry)?;
+                    tokio::fs::write(directory.join(format!("{}.json", key)), content).await?;
+                    Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+                }.await;
+
+                if let Err(e) = result {
+                    tracing::warn!(directory = %directory.display(), error = %e, "Failed to write cache entry");
+                }
+            }
+        }
+    }
+
+    async fn remove(&self, key: &str) {
+        match &self.backend {
+            Backend::Memory(cache) => {
+                cache.lock().unwrap().pop(key);
+            }
+            Backend::Disk { directory, .. } => {
+                let _ = tokio::fs::remove_file(directory.join(format!("{}.json", key))).await;
+            }
+        }
+    }
+
+    /// Sweeps the disk backend every few minutes, starting right away. The
+    /// memory backend bounds itself, so this returns at once for it.
+    pub async fn sweep_periodically(self: Arc<Self>) {
+        if matches!(self.backend, Backend::Memory(_)) {
+            return;
+        }
+        let mut ticker = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            self.sweep().await;
+        }
+    }
+
+    /// Removes expired entries from the disk backend and, beyond
+    /// `max_entries`, the oldest others.
+    pub async fn sweep(&self) {
+        let Backend::Disk { directory, max_entries } = &self.backend else {
+            return;
+        };
+        let (directory, max_entries, ttl) = (directory.clone(), *max_entries, self.ttl);
+        let result = tokio::task::spawn_blocking(move || sweep_directory(&directory, max_entries, ttl)).await;
+
+        match result {
+            Ok(Ok((0, 0))) => {}
+            Ok(Ok((expired, evicted))) => tracing::debug!(expired, evicted, "Swept response cache"),
+            Ok(Err(e)) => tracing::warn!(error = %e, "Failed to sweep response cache"),
+            Err(e) => tracing::warn!(error = %e, "Failed to sweep response cache"),
+        }
+    }
+}
+
+/// Removes the entries in `directory` written more than `ttl` ago, then the
+/// oldest ones until at most `max_entries` are left. Returns how many were
+/// removed for each reason.
+fn sweep_directory(directory: &Path, max_entries: usize, ttl: Duration) -> std::io::Result<(usize, usize)> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(e) => return Err(e),
+    };
+
+    let now = SystemTime::now();
+    let mut expired = 0;
+    let mut kept: Vec<(SystemTime, PathBuf)> = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+            continue;
+        }
+        // Entries are written once, so the file's age is the entry's
+        let Ok(written) = std::fs::metadata(&path).and_then(|metadata| metadata.modified()) else {
+            continue;
+        };
+        if now.duration_since(written).unwrap_or_default() > ttl {
+            if std::fs::remove_file(&path).is_ok() {
+                expired += 1;
+            }
+        } else {
+            kept.push((written, path));
+        }
+    }
+
+    let mut evicted = 0;
+    if kept.len() > max_entries {
+        kept.sort();
+        for (_, path) in &kept[..kept.len() - max_entries] {
+            if std::fs::remove_file(path).is_ok() {
+                evicted += 1;
+            }
+        }
+    }
+    Ok((expired, evicted))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,279 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+use crate::llms::{Completion, LlmModel, ProviderHealth, QueryError};
+use crate::llms::router::RouteRequest;
+use crate::llms::tokenizer::Tokenizer;
+
+/// What a model can do and what it costs. Every field is optional, since
+/// providers do not report this and models outside the built-in catalog are
+/// unknown until configured under `model_info`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[serde(deny_unknown_fields)]
+pub struct ModelCapabilities {
+    /// Tokens of prompt and answer together the model can attend to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u32>,
+    /// Most tokens the model writes in one answer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_vision: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_tools: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_streaming: Option<bool>,
+    /// Whether the provider can be asked for JSON-only answers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub supports_json: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+    /// Day the provider retires the model, as `YYYY-MM-DD`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecation_date: Option<String>,
+}
+
+/// Price in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+/// A feature a request can need from the model answering it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Vision,
+    Tools,
+    Streaming,
+    Json,
+}
+
+/// Published limits and prices of the providers' models, most specific name
+/// first so `gpt-4o-mini` is not taken for `gpt-4o`.
+const CATALOG: &[(&str, Known)] = &[
+    ("gpt-4o-mini", Known { context: 128_000, output: 16_384, vision: true, tools: true, json: true, price: (0.15, 0.60), retires: None }),
+    ("gpt-4o", Known { context: 128_000, output: 16_384, vision: true, tools: true, json: true, price: (2.50, 10.00), retires: None }),
+    ("o3-mini", Known { context: 200_000, output: 100_000, vision: false, tools: true, json: true, price: (1.10, 4.40), retires: None }),
+    ("o1", Known { context: 200_000, output: 100_000, vision: true, tools: true, json: true, price: (15.00, 60.00), retires: None }),
+    ("claude-3-7-sonnet", Known { context: 200_000, output: 64_000, vision: true, tools: true, json: false, price: (3.00, 15.00), retires: None }),
+    ("claude-3-5-sonnet", Known { context: 200_000, output: 8_192, vision: true, tools: true, json: false, price: (3.00, 15.00), retires: Some("2025-10-22") }),
+    ("claude-3-5-haiku", Known { context: 200_000, output: 8_192, vision: false, tools: true, json: false, price: (0.80, 4.00), retires: None }),
+];
+
+struct Known {
+    context: u32,
+    output: u32,
+    vision: bool,
+    tools: bool,
+    json: bool,
+    price: (f64, f64),
+    retires: Option<&'static str>,
+}
+
+impl ModelCapabilities {
+    /// Capabilities of an upstream model from the built-in catalog. Dated
+    /// snapshots and `-latest` names match their family, e.g.
+    /// `claude-3-5-sonnet-20241022`.
+    pub fn known(model: &str) -> Self {
+        let Some((_, known)) = CATALOG.iter().find(|(name, _)| {
+            model == *name
+                || model.strip_prefix(name).is_some_and(|suffix| {
+                    suffix == "-latest" || suffix.strip_prefix('-').is_some_and(|date| date.starts_with("20"))
+                })
+        }) else {
+            return Self::default();
+        };
+
+        Self {
+            context_window: Some(known.context),
+            max_output_tokens: Some(known.output),
+            supports_vision: Some(known.vision),
+            supports_tools: Some(known.tools),
+            supports_streaming: Some(true),
+            supports_json: Some(known.json),
+            pricing: Some(Pricing {
+                input_per_million: known.price.0,
+                output_per_million: known.price.1,
+            }),
+            deprecation_date: known.retires.map(str::to_string),
+        }
+    }
+
+    /// What holds whichever of `models` answers: the smallest limits, a
+    /// feature only if all have it, the highest prices and the earliest
+    /// deprecation. Unknown values make the result unknown, except that one
+    /// model without a feature rules it out.
+    pub fn common(models: impl IntoIterator<Item = Self>) -> Self {
+        let mut models = models.into_iter();
+        let Some(first) = models.next() else {
+            return Self::default();
+        };
+
+        models.fold(first, |common, model| Self {
+            context_window: both(common.context_window, model.context_window, u32::min),
+            max_output_tokens: both(common.max_output_tokens, model.max_output_tokens, u32::min),
+            supports_vision: all(common.supports_vision, model.supports_vision),
+            supports_tools: all(common.supports_tools, model.supports_tools),
+            supports_streaming: all(common.supports_streaming, model.supports_streaming),
+            supports_json: all(common.supports_json, model.supports_json),
+            pricing: both(common.pricing, model.pricing, |a, b| Pricing {
+                input_per_million: a.input_per_million.max(b.input_per_million),
+                output_per_million: a.output_per_million.max(b.output_per_million),
+            }),
+            deprecation_date: match (common.deprecation_date, model.deprecation_date) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        })
+    }
+
+    /// Takes the values set in `overrides` over these.
+    pub fn with_overrides(self, overrides: &Self) -> Self {
+        Self {
+            context_window: overrides.context_window.or(self.context_window),
+            max_output_tokens: overrides.max_output_tokens.or(self.max_output_tokens),
+            supports_vision: overrides.supports_vision.or(self.supports_vision),
+            supports_tools: overrides.supports_tools.or(self.supports_tools),
+            supports_streaming: overrides.supports_streaming.or(self.supports_streaming),
+            supports_json: overrides.supports_json.or(self.supports_json),
+            pricing: overrides.pricing.or(self.pricing),
+            deprecation_date: overrides.deprecation_date.clone().or(self.deprecation_date),
+        }
+    }
+
+    /// Checks the values a config file sets; `key` names them in errors.
+    pub fn validate(&self, key: &str) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.context_window == Some(0) {
+            errors.push(format!("{}: context_window must not be 0", key));
+        }
+        if self.max_output_tokens == Some(0) {
+            errors.push(format!("{}: max_output_tokens must not be 0", key));
+        }
+        if let Some(pricing) = &self.pricing {
+            if !(pricing.input_per_million >= 0.0 && pricing.output_per_million >= 0.0) {
+                errors.push(format!("{}: pricing must not be negative", key));
+            }
+        }
+        if let Some(date) = &self.deprecation_date {
+            if !is_date(date) {
+                errors.push(format!("{}: deprecation_date '{}' is not a date like 2025-10-22", key, date));
+            }
+        }
+        errors
+    }
+
+    /// One line for chat mode's model list, e.g.
+    /// `128k context, 16k output, vision, tools, $2.50/$10.00 per 1M tokens`.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(tokens) = self.context_window {
+            parts.push(format!("{} context", thousands(tokens)));
+        }
+        if let Some(tokens) = self.max_output_tokens {
+            parts.push(format!("{} output", thousands(tokens)));
+        }
+        for (feature, supported) in [
+            ("vision", self.supports_vision),
+            ("tools", self.supports_tools),
+            ("streaming", self.supports_streaming),
+            ("json", self.supports_json),
+        ] {
+            if supported == Some(true) {
+                parts.push(feature.to_string());
+            }
+        }
+        if let Some(pricing) = &self.pricing {
+            parts.push(format!("${:.2}/${:.2} per 1M tokens", pricing.input_per_million, pricing.output_per_million));
+        }
+        if let Some(date) = &self.deprecation_date {
+            parts.push(format!("deprecated {}", date));
+        }
+        parts.join(", ")
+    }
+}
+
+fn both<T>(a: Option<T>, b: Option<T>, combine: impl FnOnce(T, T) -> T) -> Option<T> {
+    Some(combine(a?, b?))
+}
+
+fn all(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+/// Shortens whole thousands of tokens to `k`, leaving other counts exact so
+/// that e.g. 128000 and 131072 stay apart.
+fn thousands(tokens: u32) -> String {
+    if tokens >= 1000 && tokens.is_multiple_of(1000) {
+        format!("{}k", tokens / 1000)
+    } else {
+        tokens.to_string()
+    }
+}
+
+fn is_date(date: &str) -> bool {
+    let parts: Vec<&str> = date.split('-').collect();
+    matches!(parts.as_slice(), [year, month, day]
+        if year.len() == 4 && month.len() == 2 && day.len() == 2
+            && parts.iter().all(|part| part.chars().all(|c| c.is_ascii_digit()))
+            && (1..=12).contains(&month.parse::<u32>().unwrap_or(0))
+            && (1..=31).contains(&day.parse::<u32>().unwrap_or(0)))
+}
+
+/// A model whose capabilities are completed or corrected from the config's
+/// `model_info`.
+pub struct DescribedModel {
+    inner: Arc<dyn LlmModel>,
+    capabilities: ModelCapabilities,
+}
+
+impl DescribedModel {
+    pub fn new(inner: Arc<dyn LlmModel>, overrides: &ModelCapabilities) -> Self {
+        let capabilities = inner.capabilities().with_overrides(overrides);
+        Self { inner, capabilities }
+    }
+}
+
+#[async_trait]
+impl LlmModel for DescribedModel {
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    fn provider(&self) -> &str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        self.capabilities.clone()
+    }
+
+    fn tokenizer(&self) -> Tokenizer {
+        self.inner.tokenizer()
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.inner.health()
+    }
+
+    async fn route(&self, request: &RouteRequest<'_>) -> Option<(String, Arc<dyn LlmModel>)> {
+        self.inner.route(request).await
+    }
+
+    async fn check(&self) -> Option<Result<(), QueryError>> {
+        self.inner.check().await
+    }
+
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
+        self.inner.query(prompt).await
+    }
+}
@@ -0,0 +1,375 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::llms::{Completion, LlmModel, ProviderHealth, QueryError};
+use crate::llms::capabilities::ModelCapabilities;
+use crate::llms::tokenizer::Tokenizer;
+use crate::llms::error::{ErrorClass, ProviderError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitBreakerSettings {
+    /// Failure rate within the window at which the circuit opens, 0.0 to 1.0.
+    pub failure_rate: f64,
+    /// Number of most recent requests the failure rate is computed over.
+    pub window: usize,
+    /// Minimum number of requests in the window before the circuit may open.
+    pub min_requests: usize,
+    /// How long the circuit stays open before a probe request is let through.
+    pub open_for: Duration,
+}
+
+struct BreakerState {
+    state: CircuitState,
+    outcomes: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+/// Clears the half-open probe flag if the probe request is dropped before
+/// it completes, so the next request may probe instead.
+struct ProbeGuard<'a> {
+    state: &'a Mutex<BreakerState>,
+    armed: bool,
+}
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.state.lock().unwrap().probe_in_flight = false;
+        }
+    }
+}
+
+/// Wraps a provider model and stops sending it requests while it is failing.
+/// Once the failure rate over the last `window` requests reaches the
+/// threshold, requests fail fast for `open_for`, after which a single probe
+/// request decides whether the circuit closes again.
+pub struct CircuitBreakerModel {
+    inner: Arc<dyn LlmModel>,
+    settings: CircuitBreakerSettings,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreakerModel {
+    pub fn new(inner: Arc<dyn LlmModel>, settings: CircuitBreakerSettings) -> Self {
+        Self {
+            inner,
+            settings,
+            state: Mutex::new(BreakerState {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::new(),
+                opened_at: None,
+                probe_in_flight: false,
+            }),
+        }
+    }
+
+    /// Decides whether a request may go through. Returns `Some(true)` if the
+    /// request is the half-open probe, `Some(false)` for a regular request and
+    /// `None` if the request has to fail fast.
+    fn admit(&self) -> Option<bool> {
+        let mut state = self.state.lock().unwrap();
+        match state.state {
+            CircuitState::Closed => Some(false),
+            CircuitState::Open => {
+                let elapsed = state.opened_at.map(|at| at.elapsed()).unwrap_or_default();
+                if elapsed >= self.settings.open_for {
+                    state.state = CircuitState::HalfOpen;
+                    state.probe_in_flight = true;
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+            CircuitState::HalfOpen => {
+                if state.probe_in_flight {
+                    None
+                } else {
+                    state.probe_in_flight = true;
+                    Some(true)
+                }
+            }
+        }
+    }
+
+    fn record(&self, success: bool, probe: bool) {
+        let mut state = self.state.lock().unwrap();
+
+        if probe {
+            state.probe_in_flight = false;
+            if success {
+                state.state = CircuitState::Closed;
+                state.outcomes.clear();
+                state.opened_at = None;
+            } else {
+                state.state = CircuitState::Open;
+                state.opened_at = Some(Instant::now());
+            }
+            return;
+        }
+
+        state.outcomes.push_back(success);
+        while state.outcomes.len() > self.settings.window {
+            state.outcomes.pop_front();
+        }
+
+        if state.state == CircuitState::Closed
+            && state.outcomes.len() >= self.settings.min_requests
+            && failure_rate(&state.outcomes) >= self.settings.failure_rate
+        {
+            tracing::warn!(
+                provider = self.inner.provider(),
+                model = self.inner.model_name(),
+                open_for_secs = self.settings.open_for.as_secs(),
+                "Opening circuit"
+            );
+            state.state = CircuitState::Open;
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+fn failure_rate(outcomes: &VecDeque<bool>) -> f64 {
+    if outcomes.is_empty() {
+        return 0.0;
+    }
+    outcomes.iter().filter(|success| !**success).count() as f64 / outcomes.len() as f64
+}
+
+/// Whether a failure says something about the provider's health, as opposed
+/// to a problem with the request itself.
+fn is_provider_failure(class: ErrorClass) -> bool {
+    matches!(
+        class,
+        ErrorClass::Overloaded
+            | ErrorClass::RateLimited
+            | ErrorClass::ServerError
+            | ErrorClass::Timeout
+            | ErrorClass::Network
+    )
+}
+
+#[async_trait]
+impl LlmModel for CircuitBreakerModel {
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    fn provider(&self) -> &str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn tokenizer(&self) -> Tokenizer {
+        self.inner.tokenizer()
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        let state = self.state.lock().unwrap();
+        let retry_in_secs = match state.state {
+            CircuitState::Open => state
+                .opened_at
+                .map(|at| self.settings.open_for.saturating_sub(at.elapsed()).as_secs()),
+            _ => None,
+        };
+
+        vec![ProviderHealth {
+            provider: self.inner.provider().to_string(),
+            model: self.inner.model_name().to_string(),
+            state: state.state,
+            failure_rate: failure_rate(&state.outcomes),
+            retry_in_secs,
+        }]
+    }
+
+    async fn check(&self) -> Option<Result<(), QueryError>> {
+        self.inner.check().await
+    }
+
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
+        let probe = match self.admit() {
+            Some(probe) => probe,
+            None => {
+                return Err(ProviderError::new(
+                    ErrorClass::CircuitOpen,
+                    format!("Circuit for {} / {} is open, not sending request", self.inner.provider(), self.inner.model_name()),
+                ).into());
+            }
+        };
+
+        let mut guard = ProbeGuard { state: &self.state, armed: probe };
+        let result = self.inner.query(prompt).await;
+        guard.armed = false;
+
+        match &result {
+            Ok(_) => self.record(true, probe),
+            Err(e) => {
+                if is_provider_failure(ErrorClass::of(e.as_ref())) {
+                    self.record(false, probe);
+                } else {
+                    // The provider answered, so it is healthy even though the request failed
+                    self.record(true, probe);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers with the next outcome of `outcomes`: `None` succeeds, an
+    /// error class fails with that class.
+    struct Scripted {
+        outcomes: Mutex<VecDeque<Option<ErrorClass>>>,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LlmModel for Scripted {
+        fn model_name(&self) -> &str {
+            "model"
+        }
+
+        fn provider(&self) -> &str {
+            "provider"
+        }
+
+        async fn query(&self, _prompt: &str) -> Result<Completion, QueryError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self
+                .outcomes
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no outcome left")
+            {
+                None => Ok(Completion {
+                    text: "ok".to_string(),
+                    model: "model".to_string(),
+                    provider: "provider".to_string(),
+                    usage: None,
+                    finish_reason: None,
+                }),
+                Some(class) => Err(ProviderError::new(class, "failed").into()),
+            }
+        }
+    }
+
+    fn breaker(
+        outcomes: impl IntoIterator<Item = Option<ErrorClass>>,
+    ) -> (Arc<Scripted>, CircuitBreakerModel) {
+        let inner = Arc::new(Scripted {
+            outcomes: Mutex::new(outcomes.into_iter().collect()),
+            calls: AtomicUsize::new(0),
+        });
+        let settings = CircuitBreakerSettings {
+            failure_rate: 0.5,
+            window: 4,
+            min_requests: 2,
+            open_for: Duration::from_millis(50),
+        };
+        (inner.clone(), CircuitBreakerModel::new(inner, settings))
+    }
+
+    fn state(breaker: &CircuitBreakerModel) -> CircuitState {
+        breaker.health()[0].state
+    }
+
+    async fn query_class(breaker: &CircuitBreakerModel) -> Option<ErrorClass> {
+        breaker
+            .query("prompt")
+            .await
+            .err()
+            .map(|e| ErrorClass::of(e.as_ref()))
+    }
+
+    #[tokio::test]
+    async fn opens_then_closes_after_a_successful_probe() {
+        let (inner, breaker) = breaker([
+            None,
+            None,
+            Some(ErrorClass::ServerError),
+            Some(ErrorClass::Timeout),
+            None,
+        ]);
+
+        assert_eq!(query_class(&breaker).await, None);
+        assert_eq!(query_class(&breaker).await, None);
+        assert_eq!(query_class(&breaker).await, Some(ErrorClass::ServerError));
+        assert_eq!(state(&breaker), CircuitState::Closed);
+        assert_eq!(query_class(&breaker).await, Some(ErrorClass::Timeout));
+        assert_eq!(state(&breaker), CircuitState::Open);
+
+        // Fails fast without calling the provider while open
+        assert_eq!(query_class(&breaker).await, Some(ErrorClass::CircuitOpen));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 4);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(query_class(&breaker).await, None);
+        assert_eq!(state(&breaker), CircuitState::Closed);
+        assert_eq!(breaker.health()[0].failure_rate, 0.0);
+    }
+
+    #[tokio::test]
+    async fn failed_probe_opens_again() {
+        let (inner, breaker) = breaker([
+            Some(ErrorClass::Network),
+            Some(ErrorClass::Network),
+            Some(ErrorClass::Overloaded),
+        ]);
+
+        query_class(&breaker).await;
+        query_class(&breaker).await;
+        assert_eq!(state(&breaker), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(query_class(&breaker).await, Some(ErrorClass::Overloaded));
+        assert_eq!(state(&breaker), CircuitState::Open);
+        assert_eq!(query_class(&breaker).await, Some(ErrorClass::CircuitOpen));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn only_one_probe_at_a_time() {
+        let (_, breaker) = breaker([Some(ErrorClass::Network), Some(ErrorClass::Network)]);
+        query_class(&breaker).await;
+        query_class(&breaker).await;
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        assert_eq!(breaker.admit(), Some(true));
+        assert_eq!(state(&breaker), CircuitState::HalfOpen);
+        assert_eq!(breaker.admit(), None);
+    }
+
+    #[tokio::test]
+    async fn request_errors_do_not_count() {
+        let (_, breaker) = breaker([
+            Some(ErrorClass::InvalidRequest),
+            Some(ErrorClass::Auth),
+            Some(ErrorClass::InvalidRequest),
+        ]);
+        for _ in 0..3 {
+            query_class(&breaker).await;
+        }
+        assert_eq!(state(&breaker), CircuitState::Closed);
+        assert_eq!(breaker.health()[0].failure_rate, 0.0);
+    }
+}
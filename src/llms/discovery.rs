@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{debug, warn};
+use crate::config::{BackendConfig, Config, DiscoveryRules, ProviderKind, DEFAULT_OLLAMA_HOST};
+use crate::llms::QueryError;
+use crate::llms::anthropic::AnthropicModel;
+use crate::llms::error::{ErrorClass, ProviderError};
+use crate::llms::ollama::OllamaModel;
+use crate::llms::openai::OpenAiModel;
+
+/// How long one provider may take to list its models.
+const LIST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The model ids each provider listed when last asked.
+#[derive(Clone, Default, PartialEq)]
+pub struct Catalog {
+    models: HashMap<ProviderKind, Vec<String>>,
+}
+
+impl Catalog {
+    /// Asks every provider with discovery rules for its models. A provider
+    /// that cannot be asked keeps the models from `previous`, so a passing
+    /// outage does not unregister them.
+    pub async fn fetch(config: &Config, previous: &Catalog) -> Catalog {
+        let mut models = HashMap::new();
+        for (provider, rules) in config.discovery.providers() {
+            let listed = tokio::time::timeout(LIST_TIMEOUT, list(provider, rules, config))
+                .await
+                .unwrap_or_else(|_| Err(ProviderError::new(ErrorClass::Timeout, "Listing models timed out".to_string()).into()));
+
+            match listed {
+                Ok(mut listed) => {
+                    listed.sort();
+                    debug!(%provider, models = listed.len(), "Fetched model list");
+                    models.insert(provider, listed);
+                }
+                Err(e) => {
+                    warn!(%provider, error = %e, "Failed to fetch model list, keeping the previous one");
+                    if let Some(listed) = previous.models.get(&provider) {
+                        models.insert(provider, listed.clone());
+                    }
+                }
+            }
+        }
+        Catalog { models }
+    }
+}
+
+async fn list(provider: ProviderKind, rules: &DiscoveryRules, config: &Config) -> Result<Vec<String>, QueryError> {
+    let no_key = |env_var: &str| -> QueryError {
+        ProviderError::new(ErrorClass::Auth, format!("No API key: set {} or api_keys.{}", env_var, provider)).into()
+    };
+
+    match provider {
+        ProviderKind::OpenAi => {
+            let api_key = config.secrets.api_key(config.api_keys.openai.as_ref(), "OPENAI_API_KEY")
+                .ok_or_else(|| no_key("OPENAI_API_KEY"))?;
+            let mut model = OpenAiModel::new(api_key, String::new());
+            if let Some(base_url) = &rules.base_url {
+                model = model.with_base_url(base_url.clone());
+            }
+            model.available_models().await
+        }
+        ProviderKind::Anthropic => {
+            let api_key = config.secrets.api_key(config.api_keys.anthropic.as_ref(), "ANTHROPIC_API_KEY")
+                .ok_or_else(|| no_key("ANTHROPIC_API_KEY"))?;
+            let mut model = AnthropicModel::new(api_key, String::new());
+            if let Some(base_url) = &rules.base_url {
+                model = model.with_base_url(base_url.clone());
+            }
+            model.available_models().await
+        }
+        ProviderKind::Ollama => OllamaModel::new(ollama_host(rules, config), String::new()).available_models().await,
+    }
+}
+
+fn ollama_host(rules: &DiscoveryRules, config: &Config) -> String {
+    rules.base_url.clone()
+        .or_else(|| config.ollama.as_ref().map(|ollama| ollama.host.clone()))
+        .unwrap_or_else(|| DEFAULT_OLLAMA_HOST.to_string())
+}
+
+/// The models of `catalog` the rules select, with the name each is
+/// registered under, in a stable order.
+pub fn backends(config: &Config, catalog: &Catalog) -> Vec<(String, BackendConfig)> {
+    let mut backends = Vec::new();
+    for (provider, rules) in config.discovery.providers() {
+        let Some(models) = catalog.models.get(&provider) else {
+            continue;
+        };
+
+        let (api_key, base_url) = match provider {
+            ProviderKind::OpenAi => (config.api_keys.openai.clone(), rules.base_url.clone()),
+            ProviderKind::Anthropic => (config.api_keys.anthropic.clone(), rules.base_url.clone()),
+            ProviderKind::Ollama => (None, Some(ollama_host(rules, config))),
+        };
+
+        for model in models.iter().filter(|model| selects(rules, model)) {
+            backends.push((alias(provider, rules, model), BackendConfig {
+                provider,
+                model: model.clone(),
+                api_key: api_key.clone(),
+                base_url: base_url.clone(),
+                organization: None,
+                weight: 1,
+            }));
+        }
+    }
+    backends
+}
+
+fn selects(rules: &DiscoveryRules, model: &str) -> bool {
+    rules.include.iter().any(|pattern| glob(pattern, model))
+        && !rules.exclude.iter().any(|pattern| glob(pattern, model))
+}
+
+/// Fills in the alias template. Ollama's default `:latest` tag is left out
+/// of `{model}`, as it is when running `ollama run`.
+fn alias(provider: ProviderKind, rules: &DiscoveryRules, model: &str) -> String {
+    let model = match provider {
+        ProviderKind::Ollama => model.strip_suffix(":latest").unwrap_or(model),
+        _ => model,
+    };
+    rules.alias
+        .replace("{provider}", &provider.to_string())
+        .replace("{model}", model)
+}
+
+/// Matches `text` against a pattern where `*` stands for any run of
+/// characters and `?` for a single one.
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was and how much of the text it has taken
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob("gpt-4o", "gpt-4o"));
+        assert!(!glob("gpt-4o", "gpt-4o-mini"));
+        assert!(glob("gpt-*", "gpt-4o-mini"));
+        assert!(glob("gpt-*", "gpt-"));
+        assert!(!glob("gpt-*", "chatgpt-4o"));
+        assert!(glob("*", ""));
+        assert!(glob("*-mini", "gpt-4o-mini"));
+        assert!(glob("gpt-?o", "gpt-4o"));
+        assert!(!glob("gpt-?o", "gpt-o"));
+        assert!(glob("claude-*-sonnet-*", "claude-3-5-sonnet-latest"));
+        assert!(!glob("claude-*-sonnet-*", "claude-3-5-haiku-latest"));
+        // Backtracks past earlier partial matches of the segment after `*`
+        assert!(glob("*a*b", "aab-ab"));
+        assert!(glob("llama*:??b", "llama3.1:70b"));
+        assert!(glob("qwén*", "qwén2.5"));
+    }
+
+    #[test]
+    fn exclude_overrides_include() {
+        let rules = DiscoveryRules {
+            include: vec!["gpt-*".to_string(), "o1".to_string()],
+            exclude: vec!["*-audio-*".to_string()],
+            alias: "{provider}/{model}".to_string(),
+            base_url: None,
+        };
+        assert!(selects(&rules, "gpt-4o"));
+        assert!(selects(&rules, "o1"));
+        assert!(!selects(&rules, "o1-mini"));
+        assert!(!selects(&rules, "gpt-4o-audio-preview"));
+        assert_eq!(alias(ProviderKind::OpenAi, &rules, "gpt-4o"), "openai/gpt-4o");
+        assert_eq!(alias(ProviderKind::Ollama, &rules, "llama3:latest"), "ollama/llama3");
+    }
+}
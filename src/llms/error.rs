@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Coarse classification of provider failures, used to decide whether a
+/// request may be retried against another model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    Overloaded,
+    RateLimited,
+    ServerError,
+    Timeout,
+    Network,
+    Auth,
+    InvalidRequest,
+    CircuitOpen,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            529 => ErrorClass::Overloaded,
+            429 => ErrorClass::RateLimited,
+            401 | 403 => ErrorClass::Auth,
+            408 => ErrorClass::Timeout,
+            500..=599 => ErrorClass::ServerError,
+            400..=499 => ErrorClass::InvalidRequest,
+            _ => ErrorClass::Other,
+        }
+    }
+
+    /// Classifies an error returned from `LlmModel::query`.
+    pub fn of(error: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<ProviderError>() {
+            return error.class;
+        }
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            if error.is_timeout() {
+                return ErrorClass::Timeout;
+            }
+            if let Some(status) = error.status() {
+                return ErrorClass::from_status(status.as_u16());
+            }
+            // Connections refused, reset or closed before the answer was
+            // complete
+            if error.is_connect() || error.is_request() || error.is_body() {
+                return ErrorClass::Network;
+            }
+        }
+        ErrorClass::Other
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorClass::Overloaded => "overloaded",
+            ErrorClass::RateLimited => "rate_limited",
+            ErrorClass::ServerError => "server_error",
+            ErrorClass::Timeout => "timeout",
+            ErrorClass::Network => "network",
+            ErrorClass::Auth => "auth",
+            ErrorClass::InvalidRequest => "invalid_request",
+            ErrorClass::CircuitOpen => "circuit_open",
+            ErrorClass::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProviderError {
+    pub class: ErrorClass,
+    pub message: String,
+}
+
+impl ProviderError {
+    pub fn new(class: ErrorClass, message: impl Into<String>) -> Self {
+        Self {
+            class,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ProviderError {}
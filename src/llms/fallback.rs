@@ -0,0 +1,171 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use crate::llms::{Completion, LlmModel, ProviderHealth, QueryError};
+use crate::llms::capabilities::ModelCapabilities;
+use crate::llms::tokenizer::Tokenizer;
+use crate::llms::error::{ErrorClass, ProviderError};
+use crate::metrics::metrics;
+
+/// A virtual model that tries an ordered list of models, moving on to the
+/// next one whenever a failure falls into one of the configured classes.
+pub struct FallbackModel {
+    name: String,
+    chain: String,
+    models: Vec<(String, Arc<dyn LlmModel>)>,
+    fallback_on: Vec<ErrorClass>,
+}
+
+impl FallbackModel {
+    pub fn new(name: String, models: Vec<(String, Arc<dyn LlmModel>)>, fallback_on: Vec<ErrorClass>) -> Self {
+        let chain = models
+            .iter()
+            .map(|(alias, _)| alias.as_str())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+
+        Self {
+            name,
+            chain,
+            models,
+            fallback_on,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmModel for FallbackModel {
+    fn model_name(&self) -> &str {
+        &self.chain
+    }
+
+    fn provider(&self) -> &str {
+        "Fallback"
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::common(self.models.iter().map(|(_, model)| model.capabilities()))
+    }
+
+    fn tokenizer(&self) -> Tokenizer {
+        Tokenizer::common(self.models.iter().map(|(_, model)| model.tokenizer()))
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.models.iter().flat_map(|(_, model)| model.health()).collect()
+    }
+
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
+        let mut failures = Vec::new();
+        let mut last_class = ErrorClass::Other;
+
+        for (alias, model) in &self.models {
+            match model.query(prompt).await {
+                Ok(completion) => return Ok(completion),
+                Err(e) => {
+                    let class = ErrorClass::of(e.as_ref());
+                    if !self.fallback_on.contains(&class) {
+                        return Err(e);
+                    }
+
+                    tracing::warn!(model = %alias, fallback = %self.name, error_type = %class, error = %e, "Model failed, trying next model");
+                    metrics().retries.with_label_values(&[self.name.as_str(), self.provider(), "fallback"]).inc();
+                    failures.push(format!("{}: {}", alias, e));
+                    last_class = class;
+                }
+            }
+        }
+
+        Err(ProviderError::new(
+            last_class,
+            format!("All models in '{}' failed. {}", self.name, failures.join("; ")),
+        ).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llms::timeout::TimeoutModel;
+    use std::time::Duration;
+
+    /// Fails with `class`, or answers when there is none.
+    struct Failing(Option<ErrorClass>);
+
+    #[async_trait]
+    impl LlmModel for Failing {
+        fn model_name(&self) -> &str {
+            "model"
+        }
+
+        fn provider(&self) -> &str {
+            "provider"
+        }
+
+        async fn query(&self, _prompt: &str) -> Result<Completion, QueryError> {
+            match self.0 {
+                Some(class) => Err(ProviderError::new(class, "failed").into()),
+                None => Ok(Completion {
+                    text: "answer".to_string(),
+                    model: "model".to_string(),
+                    provider: "provider".to_string(),
+                    usage: None,
+                    finish_reason: None,
+                }),
+            }
+        }
+    }
+
+    /// Never answers.
+    struct Hanging;
+
+    #[async_trait]
+    impl LlmModel for Hanging {
+        fn model_name(&self) -> &str {
+            "hanging"
+        }
+
+        fn provider(&self) -> &str {
+            "provider"
+        }
+
+        async fn query(&self, _prompt: &str) -> Result<Completion, QueryError> {
+            std::future::pending().await
+        }
+    }
+
+    fn fallback(first: Arc<dyn LlmModel>) -> FallbackModel {
+        let config = r#"{ "name": "chain", "models": ["first", "second"] }"#;
+        let fallback_on = serde_json::from_str::<crate::config::FallbackModelConfig>(config).unwrap().fallback_on;
+        FallbackModel::new("chain".to_string(), vec![
+            ("first".to_string(), first),
+            ("second".to_string(), Arc::new(Failing(None))),
+        ], fallback_on)
+    }
+
+    #[tokio::test]
+    async fn falls_back_on_timeouts_and_network_errors_by_default() {
+        let timing_out = Arc::new(TimeoutModel::new(Arc::new(Hanging), Duration::from_millis(10)));
+        assert_eq!(fallback(timing_out).query("prompt").await.unwrap().text, "answer");
+
+        // A server closing connections without answering
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((connection, _)) = listener.accept().await {
+                drop(connection);
+            }
+        });
+        let error = reqwest::get(format!("http://{}/", address)).await.unwrap_err();
+        assert_eq!(ErrorClass::of(&error), ErrorClass::Network);
+
+        let unreachable = Arc::new(Failing(Some(ErrorClass::Network)));
+        assert_eq!(fallback(unreachable).query("prompt").await.unwrap().text, "answer");
+    }
+
+    #[tokio::test]
+    async fn returns_errors_outside_fallback_on() {
+        let rejecting = Arc::new(Failing(Some(ErrorClass::InvalidRequest)));
+        let error = fallback(rejecting).query("prompt").await.unwrap_err();
+        assert_eq!(ErrorClass::of(error.as_ref()), ErrorClass::InvalidRequest);
+    }
+}
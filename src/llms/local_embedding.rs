@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use crate::llms::{EmbeddingModel, QueryError};
+
+/// Embeds text locally by hashing words and character trigrams into a fixed
+/// number of dimensions. Far weaker than a trained embedding model, but free
+/// and good enough to recognize prompts that differ only in wording details.
+pub struct HashingEmbeddingModel {
+    dimensions: usize,
+}
+
+impl HashingEmbeddingModel {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    fn embed_one(&self, input: &str, dimensions: usize) -> Vec<f32> {
+        let mut vector = vec![0f32; dimensions];
+        let text = input.to_lowercase();
+
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+            vector[bucket(word.as_bytes(), dimensions)] += 1.0;
+
+            let padded: Vec<char> = format!(" {} ", word).chars().collect();
+            for trigram in padded.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                vector[bucket(trigram.as_bytes(), dimensions)] += 0.5;
+            }
+        }
+
+        let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|value| *value /= norm);
+        }
+        vector
+    }
+}
+
+/// FNV-1a, which unlike the standard library hasher is stable across
+/// releases, so persisted vectors stay comparable.
+fn bucket(bytes: &[u8], dimensions: usize) -> usize {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    (hash % dimensions as u64) as usize
+}
+
+#[async_trait]
+impl EmbeddingModel for HashingEmbeddingModel {
+    fn model_name(&self) -> &str {
+        "local-hashing"
+    }
+
+    fn provider(&self) -> &str {
+        "Local"
+    }
+
+    async fn embed(&self, inputs: &[String], dimensions: Option<u32>) -> Result<Vec<Vec<f32>>, QueryError> {
+        let dimensions = dimensions.map(|dimensions| dimensions as usize).unwrap_or(self.dimensions).max(1);
+        Ok(inputs.iter().map(|input| self.embed_one(input, dimensions)).collect())
+    }
+}
@@ -1,12 +1,105 @@
 pub mod openai;
 pub mod anthropic;
+pub mod ollama;
+pub mod balanced;
+pub mod cache;
+pub mod semantic_cache;
+pub mod local_embedding;
+pub mod circuit_breaker;
+pub mod fallback;
+pub mod single_flight;
+pub mod error;
 pub mod model_collection;
+pub mod readiness;
+pub mod capabilities;
+pub mod discovery;
+pub mod router;
+pub mod tokenizer;
+pub mod timeout;
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use circuit_breaker::CircuitState;
+use capabilities::ModelCapabilities;
+use router::RouteRequest;
+use tokenizer::Tokenizer;
+
+pub type QueryError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The answer to a query together with the model that produced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Completion {
+    pub text: String,
+    pub model: String,
+    pub provider: String,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// Why the model stopped, as reported by the provider (e.g. `stop`).
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+/// Token counts reported by the provider.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+/// Circuit breaker state of one provider backend.
+#[derive(Debug, Clone)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub model: String,
+    pub state: CircuitState,
+    pub failure_rate: f64,
+    pub retry_in_secs: Option<u64>,
+}
 
 #[async_trait]
 pub trait LlmModel: Send + Sync {
     fn model_name(&self) -> &str;
     fn provider(&self) -> &str;
-    async fn query(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>>;
-} 
\ No newline at end of file
+
+    /// Limits, features and prices of the model, as far as they are known.
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::default()
+    }
+
+    /// How the model splits prompts into tokens, or an approximation.
+    fn tokenizer(&self) -> Tokenizer {
+        Tokenizer::approximate()
+    }
+
+    /// Health of the provider backends behind this model. Empty for models
+    /// without a circuit breaker.
+    fn health(&self) -> Vec<ProviderHealth> {
+        Vec::new()
+    }
+
+    /// Cheaply checks that the provider is reachable and accepts the
+    /// credentials, without running a completion. `None` for models with
+    /// nothing of their own to check.
+    async fn check(&self) -> Option<Result<(), QueryError>> {
+        None
+    }
+
+    /// The model that should answer `request`, with the name it is
+    /// registered under, for models that hand requests on to others. `None`
+    /// for models that answer themselves.
+    async fn route(&self, _request: &RouteRequest<'_>) -> Option<(String, Arc<dyn LlmModel>)> {
+        None
+    }
+
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError>;
+}
+
+#[async_trait]
+pub trait EmbeddingModel: Send + Sync {
+    fn model_name(&self) -> &str;
+    fn provider(&self) -> &str;
+    /// Returns one vector per input, in input order. `dimensions` asks for
+    /// shortened vectors from models that support it.
+    async fn embed(&self, inputs: &[String], dimensions: Option<u32>) -> Result<Vec<Vec<f32>>, QueryError>;
+}
@@ -1,51 +1,587 @@
 use std::collections::HashMap;
-use super::LlmModel;
-use super::openai::OpenAiModel;
+use std::sync::Arc;
+use std::time::Duration;
+use super::{EmbeddingModel, LlmModel, ProviderHealth};
+use super::openai::{OpenAiEmbeddingModel, OpenAiModel};
 use super::anthropic::AnthropicModel;
+use super::ollama::{OllamaEmbeddingModel, OllamaModel};
+use super::local_embedding::HashingEmbeddingModel;
+use super::balanced::BalancedModel;
+use super::fallback::FallbackModel;
+use super::router::{Classifier, RouteConditions, RouteRule, RouterModel};
+use super::circuit_breaker::{CircuitBreakerModel, CircuitBreakerSettings};
+use super::capabilities::DescribedModel;
+use super::timeout::TimeoutModel;
+use super::discovery::{self, Catalog};
+use crate::config::{BackendConfig, BalancedModelConfig, Config, ProviderKind, RouterModelConfig, SecretsConfig, DEFAULT_OLLAMA_HOST};
+use crate::secret::Secret;
+
+/// Names of the models registered from the provider environment variables.
+pub const BUILTIN_MODELS: [&str; 4] = ["gpt-4o", "gpt-4o-mini", "o3-mini", "Sonnet 3.5"];
 
 pub struct ModelCollection {
-    models: HashMap<String, Box<dyn LlmModel>>,
+    models: HashMap<String, Arc<dyn LlmModel>>,
+    embedding_models: HashMap<String, Arc<dyn EmbeddingModel>>,
+    /// Other names of models, mapped to the name they are registered under.
+    aliases: HashMap<String, String>,
+    default_model: Option<String>,
+    stateful: StatefulModels,
+}
+
+/// Settings a provider model is built from.
+#[derive(PartialEq)]
+struct ProviderKey {
+    /// Name the model is registered under, or the balanced model's name.
+    name: String,
+    /// The backend's position in a balanced model.
+    position: Option<usize>,
+    backend: BackendConfig,
+    secrets: SecretsConfig,
+    timeout: Duration,
+    breaker: Option<CircuitBreakerSettings>,
+}
+
+/// Settings a balanced model is built from.
+#[derive(PartialEq)]
+struct BalancedKey {
+    config: BalancedModelConfig,
+    secrets: SecretsConfig,
+    timeout: Duration,
+    breaker: Option<CircuitBreakerSettings>,
+}
+
+/// The models of a collection that keep state between requests, circuit
+/// breakers and ejected backends, by the settings they were built from. A
+/// rebuild takes over those whose settings did not change, so a reload
+/// does not close an open circuit or bring back an ejected backend.
+#[derive(Default)]
+struct StatefulModels {
+    providers: Vec<(ProviderKey, Arc<dyn LlmModel>)>,
+    balanced: Vec<(BalancedKey, Arc<dyn LlmModel>)>,
+}
+
+impl StatefulModels {
+    /// The provider model built from `key`: the one in `previous` if it was
+    /// built from the same settings, otherwise a new one behind its timeout
+    /// and circuit breaker.
+    fn provider(&mut self, previous: Option<&StatefulModels>, key: ProviderKey) -> Option<Arc<dyn LlmModel>> {
+        let model = match previous.and_then(|previous| previous.providers.iter().find(|(built, _)| *built == key)) {
+            Some((_, model)) => model.clone(),
+            None => ModelCollection::guarded(ModelCollection::build_backend(&key.name, &key.backend, &key.secrets)?, key.timeout, &key.breaker),
+        };
+        self.providers.push((key, model.clone()));
+        Some(model)
+    }
 }
 
 impl ModelCollection {
-    pub fn new() -> Self {
-        let mut models : HashMap<String, Box<dyn LlmModel>> = HashMap::new();
-        
+    /// Builds the configured models and those `catalog` lists that the
+    /// discovery rules select. Models of `previous` whose settings did not
+    /// change are taken over with their state.
+    pub fn new(config: &Config, catalog: &Catalog, previous: Option<&ModelCollection>) -> Self {
+        let mut models : HashMap<String, Arc<dyn LlmModel>> = HashMap::new();
+        let previous = previous.map(|previous| &previous.stateful);
+        let mut stateful = StatefulModels::default();
+        let breaker = config.circuit_breaker.settings();
+        let key = |name: &str, backend: BackendConfig| ProviderKey {
+            name: name.to_string(),
+            position: None,
+            backend,
+            secrets: config.secrets.clone(),
+            timeout: config.timeouts.for_model(name),
+            breaker: breaker.clone(),
+        };
+        let builtin = |provider: ProviderKind, model: &str, api_key: &Option<Secret>| BackendConfig {
+            provider,
+            model: model.to_string(),
+            api_key: api_key.clone(),
+            base_url: None,
+            organization: None,
+            weight: 1,
+        };
+
         // OpenAI Models
-        if let Ok(api_key) = std::env::var("OPENAI_API_KEY") {
-            models.insert(
-                "gpt-4o".to_string(),
-                Box::new(OpenAiModel::new(api_key.clone(), "gpt-4o".to_string()))
-            );
-            models.insert(
-                "gpt-4o-mini".to_string(),
-                Box::new(OpenAiModel::new(api_key.clone(), "gpt-4o-mini".to_string()))
-            );
-            models.insert(
-                "o3-mini".to_string(),
-                Box::new(OpenAiModel::new(api_key, "o3-mini".to_string()))
-            );
+        if config.secrets.api_key(config.api_keys.openai.as_ref(), "OPENAI_API_KEY").is_some() {
+            for name in ["gpt-4o", "gpt-4o-mini", "o3-mini"] {
+                let backend = builtin(ProviderKind::OpenAi, name, &config.api_keys.openai);
+                if let Some(model) = stateful.provider(previous, key(name, backend)) {
+                    models.insert(name.to_string(), model);
+                }
+            }
         }
-        
+
         // Anthropic Models
-        if let Ok(api_key) = std::env::var("ANTHROPIC_API_KEY") {
+        if config.secrets.api_key(config.api_keys.anthropic.as_ref(), "ANTHROPIC_API_KEY").is_some() {
+            let backend = builtin(ProviderKind::Anthropic, "claude-3-5-sonnet-latest", &config.api_keys.anthropic);
+            if let Some(model) = stateful.provider(previous, key("Sonnet 3.5", backend)) {
+                models.insert("Sonnet 3.5".to_string(), model);
+            }
+        }
+
+        // Ollama Models
+        if let Some(ollama) = &config.ollama {
+            for name in &ollama.models {
+                let backend = BackendConfig {
+                    base_url: Some(ollama.host.clone()),
+                    ..builtin(ProviderKind::Ollama, name, &None)
+                };
+                if let Some(model) = stateful.provider(previous, key(name, backend)) {
+                    models.insert(name.clone(), model);
+                }
+            }
+        }
+
+        // Discovered models, unless the name is already taken
+        for (name, backend) in discovery::backends(config, catalog) {
+            if models.contains_key(&name) {
+                tracing::debug!("Discovered model '{}' is already registered, skipping it", name);
+                continue;
+            }
+            if let Some(model) = stateful.provider(previous, key(&name, backend)) {
+                models.insert(name, model);
+            }
+        }
+
+        // Load balanced models, which replace an environment-configured model of the same name
+        for balanced in &config.balanced_models {
+            let balanced_key = BalancedKey {
+                config: balanced.clone(),
+                secrets: config.secrets.clone(),
+                timeout: config.timeouts.for_model(&balanced.name),
+                breaker: breaker.clone(),
+            };
+            if let Some((_, model)) = previous.and_then(|previous| previous.balanced.iter().find(|(built, _)| *built == balanced_key)) {
+                models.insert(balanced.name.clone(), model.clone());
+                stateful.balanced.push((balanced_key, model.clone()));
+                continue;
+            }
+
+            let backends: Vec<(Arc<dyn LlmModel>, u32)> = balanced.backends
+                .iter()
+                .enumerate()
+                .filter_map(|(index, backend)| {
+                    let backend_key = ProviderKey {
+                        position: Some(index),
+                        timeout: balanced_key.timeout,
+                        ..key(&balanced.name, backend.clone())
+                    };
+                    stateful.provider(previous, backend_key).map(|model| (model, backend.weight))
+                })
+                .collect();
+
+            if backends.is_empty() {
+                tracing::warn!("Balanced model '{}' has no usable backends, not registering it", balanced.name);
+                continue;
+            }
+
+            let mut upstream_models: Vec<&str> = balanced.backends.iter().map(|backend| backend.model.as_str()).collect();
+            upstream_models.sort_unstable();
+            upstream_models.dedup();
+
+            let model: Arc<dyn LlmModel> = Arc::new(BalancedModel::new(
+                balanced.name.clone(),
+                upstream_models.join(", "),
+                balanced.strategy,
+                backends,
+                balanced.eject_after,
+                Duration::from_secs(balanced.eject_for_secs),
+            ));
+            models.insert(balanced.name.clone(), model.clone());
+            stateful.balanced.push((balanced_key, model));
+        }
+
+        // Fallback chains see the configured capabilities of their models
+        for (name, model) in models.iter_mut() {
+            *model = Self::described(name, model.clone(), config);
+        }
+
+        // Fallback chains, which may also refer to chains defined before them
+        for fallback in &config.fallback_models {
+            let mut chain = Vec::new();
+            for name in &fallback.models {
+                match models.get(name) {
+                    Some(model) => chain.push((name.clone(), model.clone())),
+                    None => tracing::warn!("Fallback model '{}' refers to unknown model '{}', skipping it", fallback.name, name),
+                }
+            }
+
+            if chain.is_empty() {
+                tracing::warn!("Fallback model '{}' has no available models, not registering it", fallback.name);
+                continue;
+            }
+
             models.insert(
-                "Sonnet 3.5".to_string(),
-                Box::new(AnthropicModel::new(api_key.clone(), "claude-3-5-sonnet-latest".to_string()))
+                fallback.name.clone(),
+                Self::described(
+                    &fallback.name,
+                    Arc::new(FallbackModel::new(fallback.name.clone(), chain, fallback.fallback_on.clone())),
+                    config,
+                )
             );
         }
-        
-        Self { models }
+
+        // Router models, which may also refer to routers defined before them
+        for router in &config.router_models {
+            if let Some(model) = Self::router(router, &models) {
+                models.insert(router.name.clone(), Self::described(&router.name, model, config));
+            }
+        }
+
+        let embedding_models = Self::embedding_models(config);
+
+        let mut aliases = HashMap::new();
+        for (name, names) in &config.aliases {
+            if !models.contains_key(name) {
+                tracing::warn!("Aliases for unknown model '{}', skipping them", name);
+                continue;
+            }
+            for alias in names {
+                aliases.insert(alias.clone(), name.clone());
+            }
+        }
+
+        Self { models, embedding_models, aliases, default_model: config.default_model.clone(), stateful }
+    }
+
+    fn embedding_models(config: &Config) -> HashMap<String, Arc<dyn EmbeddingModel>> {
+        let mut models : HashMap<String, Arc<dyn EmbeddingModel>> = HashMap::new();
+
+        if let Some(api_key) = config.secrets.api_key(config.api_keys.openai.as_ref(), "OPENAI_API_KEY") {
+            for model in ["text-embedding-3-small", "text-embedding-3-large"] {
+                models.insert(
+                    model.to_string(),
+                    Arc::new(OpenAiEmbeddingModel::new(api_key.clone(), model.to_string()))
+                );
+            }
+        }
+
+        if let Some(ollama) = &config.ollama {
+            for model in &ollama.embedding_models {
+                models.insert(
+                    model.clone(),
+                    Arc::new(OllamaEmbeddingModel::new(ollama.host.clone(), model.clone()))
+                );
+            }
+        }
+
+        // Always available, useful for testing without provider access
+        models.insert(
+            "local-hashing".to_string(),
+            Arc::new(HashingEmbeddingModel::new(256))
+        );
+
+        models
+    }
+
+    /// Puts a provider model behind its timeout and, if enabled, a circuit
+    /// breaker, which then counts timeouts as failures.
+    fn guarded(model: Arc<dyn LlmModel>, timeout: Duration, settings: &Option<CircuitBreakerSettings>) -> Arc<dyn LlmModel> {
+        let model: Arc<dyn LlmModel> = Arc::new(TimeoutModel::new(model, timeout));
+        match settings {
+            Some(settings) => Arc::new(CircuitBreakerModel::new(model, settings.clone())),
+            None => model,
+        }
+    }
+
+    /// Builds a router from the models registered so far. Rules naming an
+    /// unknown model are left out; without its default model the router is
+    /// not registered.
+    fn router(router: &RouterModelConfig, models: &HashMap<String, Arc<dyn LlmModel>>) -> Option<Arc<dyn LlmModel>> {
+        let Some(default) = models.get(&router.default) else {
+            tracing::warn!("Router model '{}' refers to unknown default model '{}', not registering it", router.name, router.default);
+            return None;
+        };
+
+        let mut rules = Vec::new();
+        for (index, rule) in router.rules.iter().enumerate() {
+            let Some(model) = models.get(&rule.model) else {
+                tracing::warn!("Router model '{}' refers to unknown model '{}' in rule {}, skipping the rule", router.name, rule.model, index + 1);
+                continue;
+            };
+            rules.push(RouteRule {
+                conditions: RouteConditions {
+                    min_tokens: rule.min_tokens,
+                    max_tokens: rule.max_tokens,
+                    requires: rule.requires.clone(),
+                    callers: rule.callers.clone(),
+                    verdict: rule.classifier.clone(),
+                },
+                target: rule.model.clone(),
+                model: model.clone(),
+            });
+        }
+
+        let classifier = router.classifier.as_ref().and_then(|classifier| match models.get(&classifier.model) {
+            Some(model) => Some(Classifier {
+                model: model.clone(),
+                instructions: classifier.instructions.clone(),
+                max_prompt_chars: classifier.max_prompt_chars,
+                timeout: Duration::from_millis(classifier.timeout_ms),
+            }),
+            None => {
+                tracing::warn!("Router model '{}' refers to unknown classifier model '{}', its rules will not match", router.name, classifier.model);
+                None
+            }
+        });
+
+        Some(Arc::new(RouterModel::new(router.name.clone(), rules, (router.default.clone(), default.clone()), classifier)))
+    }
+
+    /// Applies the capabilities configured under `model_info` for `name`.
+    fn described(name: &str, model: Arc<dyn LlmModel>, config: &Config) -> Arc<dyn LlmModel> {
+        match config.model_info.get(name) {
+            Some(capabilities) => Arc::new(DescribedModel::new(model, capabilities)),
+            None => model,
+        }
+    }
+
+    /// Builds a single backend of the model configured as `name`, taking the
+    /// API key from the provider's environment variable when the backend
+    /// does not specify one.
+    fn build_backend(name: &str, backend: &BackendConfig, secrets: &SecretsConfig) -> Option<Arc<dyn LlmModel>> {
+        let api_key = |env_var: &str| {
+            let api_key = secrets.api_key(backend.api_key.as_ref(), env_var);
+            if api_key.is_none() {
+                tracing::warn!("No API key for {} backend of model '{}', skipping it", env_var, backend.model);
+            }
+            api_key
+        };
+
+        match backend.provider {
+            ProviderKind::OpenAi => {
+                let mut model = OpenAiModel::new(api_key("OPENAI_API_KEY")?, backend.model.clone());
+                if let Some(base_url) = &backend.base_url {
+                    model = model.with_base_url(base_url.clone());
+                }
+                if let Some(organization) = &backend.organization {
+                    model = model.with_organization(organization.clone());
+                }
+                Some(Arc::new(model))
+            }
+            ProviderKind::Anthropic => {
+                let mut model = AnthropicModel::new(api_key("ANTHROPIC_API_KEY")?, backend.model.clone()).with_name(name.to_string());
+                if let Some(base_url) = &backend.base_url {
+                    model = model.with_base_url(base_url.clone());
+                }
+                Some(Arc::new(model))
+            }
+            ProviderKind::Ollama => {
+                let host = backend.base_url.clone().unwrap_or_else(|| DEFAULT_OLLAMA_HOST.to_string());
+                Some(Arc::new(OllamaModel::new(host, backend.model.clone())))
+            }
+        }
+    }
+
+    pub fn get_model(&self, model_name: &str) -> Option<Arc<dyn LlmModel>> {
+        self.find_model(model_name).map(|(_, model)| model)
+    }
+
+    /// Looks a model up by its name or one of its aliases, ignoring case
+    /// when nothing matches exactly. Returns the name the model is
+    /// registered under with it. Names that differ only in case from
+    /// several models match none of them.
+    pub fn find_model(&self, model_name: &str) -> Option<(String, Arc<dyn LlmModel>)> {
+        let name = find_name(&self.models, &self.aliases, model_name)?;
+        Some((name.clone(), self.models[name].clone()))
+    }
+
+    /// The model used when a request names none.
+    pub fn default_model(&self) -> Option<(String, Arc<dyn LlmModel>)> {
+        self.find_model(self.default_model.as_deref()?)
     }
-    
-    pub fn get_model(&self, model_name: &str) -> Option<&Box<dyn LlmModel>> {
-        self.models.get(model_name)
+
+    /// Other names `model_name` is known by, sorted.
+    pub fn aliases_of(&self, model_name: &str) -> Vec<String> {
+        let mut aliases: Vec<String> = self.aliases
+            .iter()
+            .filter(|(_, name)| *name == model_name)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    pub fn is_default(&self, model_name: &str) -> bool {
+        self.default_model().is_some_and(|(name, _)| name == model_name)
     }
-    
+
+    /// Says that `model_name` is unknown, suggesting similar names.
+    pub fn model_not_found(&self, model_name: &str) -> String {
+        let names = self.models.keys().chain(self.aliases.keys());
+        not_found("Model", model_name, names)
+    }
+
     pub fn list_models(&self) -> Vec<(&String, &str, &str)> {
         self.models
             .iter()
             .map(|(name, model)| (name, model.provider(), model.model_name()))
             .collect()
     }
-} 
\ No newline at end of file
+
+    /// Looks an embedding model up by its name, ignoring case when nothing
+    /// matches exactly. Returns the name it is registered under with it.
+    pub fn find_embedding_model(&self, model_name: &str) -> Option<(String, Arc<dyn EmbeddingModel>)> {
+        let no_aliases = HashMap::new();
+        let name = find_name(&self.embedding_models, &no_aliases, model_name)?;
+        Some((name.clone(), self.embedding_models[name].clone()))
+    }
+
+    pub fn embedding_model_not_found(&self, model_name: &str) -> String {
+        not_found("Embedding model", model_name, self.embedding_models.keys())
+    }
+
+    pub fn list_embedding_models(&self) -> Vec<(&String, &str, &str)> {
+        self.embedding_models
+            .iter()
+            .map(|(name, model)| (name, model.provider(), model.model_name()))
+            .collect()
+    }
+
+    pub fn health(&self) -> Vec<(&String, Vec<ProviderHealth>)> {
+        self.models
+            .iter()
+            .map(|(name, model)| (name, model.health()))
+            .collect()
+    }
+}
+
+/// The key of `map` that `name` refers to: the key itself, an alias of it,
+/// or either in other case if that is unambiguous.
+fn find_name<'a, T>(map: &'a HashMap<String, T>, aliases: &'a HashMap<String, String>, name: &str) -> Option<&'a String> {
+    if let Some((key, _)) = map.get_key_value(name) {
+        return Some(key);
+    }
+    if let Some(key) = aliases.get(name) {
+        return map.get_key_value(key).map(|(key, _)| key);
+    }
+
+    let mut matches = map.keys()
+        .filter(|key| key.eq_ignore_ascii_case(name))
+        .chain(aliases.iter().filter(|(alias, _)| alias.eq_ignore_ascii_case(name)).map(|(_, key)| key))
+        .collect::<Vec<_>>();
+    matches.sort();
+    matches.dedup();
+    match matches.as_slice() {
+        [key] => map.get_key_value(key.as_str()).map(|(key, _)| key),
+        _ => None,
+    }
+}
+
+/// How many names "did you mean" offers at most.
+const MAX_SUGGESTIONS: usize = 3;
+
+fn not_found<'a>(kind: &str, name: &str, known: impl Iterator<Item = &'a String>) -> String {
+    let suggestions = suggestions(name, known);
+    match suggestions.as_slice() {
+        [] => format!("{} '{}' not found", kind, name),
+        [suggestion] => format!("{} '{}' not found, did you mean '{}'?", kind, name, suggestion),
+        _ => format!(
+            "{} '{}' not found, did you mean one of {}?",
+            kind,
+            name,
+            suggestions.iter().map(|suggestion| format!("'{}'", suggestion)).collect::<Vec<_>>().join(", "),
+        ),
+    }
+}
+
+/// Known names close to `name`: a few typos away or containing it, closest
+/// first. Case is ignored.
+fn suggestions<'a>(name: &str, known: impl Iterator<Item = &'a String>) -> Vec<&'a str> {
+    let name = name.to_lowercase();
+    if name.is_empty() {
+        return Vec::new();
+    }
+    let max_distance = (name.chars().count() / 3).clamp(1, 3);
+
+    let mut close: Vec<(usize, &str)> = known
+        .filter_map(|candidate| {
+            let lowercase = candidate.to_lowercase();
+            let distance = edit_distance(&name, &lowercase);
+            if distance <= max_distance {
+                Some((distance, candidate.as_str()))
+            } else if name.chars().count() >= 3 && lowercase.contains(&name) {
+                Some((max_distance + 1, candidate.as_str()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    close.sort();
+    close.dedup();
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, candidate)| candidate).collect()
+}
+
+/// Levenshtein distance in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection() -> ModelCollection {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "ollama": {"models": ["llama3.2", "Llama3.2-vision", "mistral"]},
+            "aliases": {"mistral": ["fast", "small"]},
+            "default_model": "fast",
+        })).unwrap();
+        ModelCollection::new(&config, &Catalog::default(), None)
+    }
+
+    fn found(models: &ModelCollection, name: &str) -> Option<String> {
+        models.find_model(name).map(|(name, _)| name)
+    }
+
+    #[test]
+    fn resolves_names_aliases_and_case() {
+        let models = collection();
+        assert_eq!(found(&models, "mistral").as_deref(), Some("mistral"));
+        assert_eq!(found(&models, "fast").as_deref(), Some("mistral"));
+        assert_eq!(found(&models, "SMALL").as_deref(), Some("mistral"));
+        assert_eq!(found(&models, "LLAMA3.2").as_deref(), Some("llama3.2"));
+        assert_eq!(models.default_model().map(|(name, _)| name).as_deref(), Some("mistral"));
+        assert_eq!(models.aliases_of("mistral"), ["fast", "small"]);
+        assert!(models.is_default("mistral"));
+    }
+
+    #[test]
+    fn exact_match_wins_over_other_case() {
+        let mut map: HashMap<String, ()> = HashMap::new();
+        map.insert("Model".to_string(), ());
+        map.insert("model".to_string(), ());
+        let aliases = HashMap::new();
+        assert_eq!(find_name(&map, &aliases, "Model").map(String::as_str), Some("Model"));
+        // Ambiguous without the exact case
+        assert_eq!(find_name(&map, &aliases, "MODEL"), None);
+    }
+
+    #[test]
+    fn suggests_close_names() {
+        let models = collection();
+        assert_eq!(models.model_not_found("mistrl"), "Model 'mistrl' not found, did you mean 'mistral'?");
+        assert_eq!(
+            models.model_not_found("llama"),
+            "Model 'llama' not found, did you mean one of 'Llama3.2-vision', 'llama3.2'?",
+        );
+        assert_eq!(models.model_not_found("qwen"), "Model 'qwen' not found");
+        assert_eq!(models.model_not_found(""), "Model '' not found");
+    }
+
+    #[test]
+    fn counts_edits_in_characters() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("é", "e"), 1);
+    }
+}
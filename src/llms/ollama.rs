@@ -0,0 +1,253 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use crate::llms::{Completion, EmbeddingModel, LlmModel, QueryError, TokenUsage};
+use crate::llms::capabilities::ModelCapabilities;
+use crate::llms::error::{ErrorClass, ProviderError};
+use crate::telemetry;
+
+pub struct OllamaModel {
+    client: Client,
+    host: String,
+    model: String,
+}
+
+#[derive(Serialize)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct OllamaRequest {
+    model: String,
+    messages: Vec<Message>,
+    stream: bool,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponseMessage {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    message: OllamaResponseMessage,
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+    done_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OllamaError {
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct OllamaTags {
+    models: Vec<OllamaTag>,
+}
+
+#[derive(Deserialize)]
+struct OllamaTag {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct OllamaEmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl OllamaModel {
+    pub fn new(host: String, model: String) -> Self {
+        Self {
+            client: Client::new(),
+            host: host.trim_end_matches('/').to_string(),
+            model,
+        }
+    }
+
+    /// Names of the models pulled on the host, e.g. `llama3.2:latest`.
+    pub async fn available_models(&self) -> Result<Vec<String>, QueryError> {
+        let response = self.client
+            .get(format!("{}/api/tags", self.host))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ProviderError::new(
+                ErrorClass::from_status(status.as_u16()),
+                format!("Ollama API returned {}", status),
+            ).into());
+        }
+        let tags: OllamaTags = response.json().await?;
+        Ok(tags.models.into_iter().map(|model| model.name).collect())
+    }
+}
+
+#[async_trait]
+impl LlmModel for OllamaModel {
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn provider(&self) -> &str {
+        "Ollama"
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::known(&self.model)
+    }
+
+    async fn check(&self) -> Option<Result<(), QueryError>> {
+        Some(self.available_models().await.map(|_| ()))
+    }
+
+    #[tracing::instrument(
+        name = "provider_call",
+        skip_all,
+        fields(
+            provider = "Ollama",
+            model = %self.model,
+            otel.name = %format!("chat {}", self.model),
+            otel.kind = "client",
+            gen_ai.operation.name = "chat",
+            gen_ai.system = "ollama",
+            gen_ai.request.model = %self.model,
+            gen_ai.response.model = tracing::field::Empty,
+            gen_ai.usage.input_tokens = tracing::field::Empty,
+            gen_ai.usage.output_tokens = tracing::field::Empty,
+            gen_ai.response.finish_reasons = tracing::field::Empty,
+        ),
+    )]
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
+        let request = OllamaRequest {
+            model: self.model.clone(),
+            messages: vec![Message {
+                role: String::from("user"),
+                content: prompt.to_string(),
+            }],
+            stream: false,
+        };
+
+        let response = self.client
+            .post(format!("{}/api/chat", self.host))
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = response.status().as_u16();
+        let response_text = response.text().await?;
+
+        if let Ok(response_data) = serde_json::from_str::<OllamaResponse>(&response_text) {
+            let usage = match (response_data.prompt_eval_count, response_data.eval_count) {
+                (Some(prompt_tokens), Some(completion_tokens)) => Some(TokenUsage { prompt_tokens, completion_tokens }),
+                _ => None,
+            };
+            let completion = Completion {
+                text: response_data.message.content,
+                model: self.model.clone(),
+                provider: self.provider().to_string(),
+                usage,
+                finish_reason: response_data.done_reason,
+            };
+            telemetry::record_completion(&completion);
+            return Ok(completion);
+        }
+
+        if let Ok(error_data) = serde_json::from_str::<OllamaError>(&response_text) {
+            return Err(ProviderError::new(
+                ErrorClass::from_status(status),
+                format!("Ollama API Error: {}", error_data.error),
+            ).into());
+        }
+
+        Err(ProviderError::new(
+            ErrorClass::from_status(status),
+            format!("Unexpected API response: {}", response_text),
+        ).into())
+    }
+}
+
+pub struct OllamaEmbeddingModel {
+    client: Client,
+    host: String,
+    model: String,
+}
+
+impl OllamaEmbeddingModel {
+    pub fn new(host: String, model: String) -> Self {
+        Self {
+            client: Client::new(),
+            host: host.trim_end_matches('/').to_string(),
+            model,
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for OllamaEmbeddingModel {
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn provider(&self) -> &str {
+        "Ollama"
+    }
+
+    #[tracing::instrument(
+        name = "provider_call",
+        skip_all,
+        fields(
+            provider = "Ollama",
+            model = %self.model,
+            inputs = inputs.len(),
+            otel.name = %format!("embeddings {}", self.model),
+            otel.kind = "client",
+            gen_ai.operation.name = "embeddings",
+            gen_ai.system = "ollama",
+            gen_ai.request.model = %self.model,
+        ),
+    )]
+    async fn embed(&self, inputs: &[String], dimensions: Option<u32>) -> Result<Vec<Vec<f32>>, QueryError> {
+        let request = OllamaEmbedRequest {
+            model: &self.model,
+            input: inputs,
+            dimensions,
+        };
+
+        let response = self.client
+            .post(format!("{}/api/embed", self.host))
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = response.status().as_u16();
+        let response_text = response.text().await?;
+
+        if let Ok(response_data) = serde_json::from_str::<OllamaEmbedResponse>(&response_text) {
+            return Ok(response_data.embeddings);
+        }
+
+        if let Ok(error_data) = serde_json::from_str::<OllamaError>(&response_text) {
+            return Err(ProviderError::new(
+                ErrorClass::from_status(status),
+                format!("Ollama API Error: {}", error_data.error),
+            ).into());
+        }
+
+        Err(ProviderError::new(
+            ErrorClass::from_status(status),
+            format!("Unexpected API response: {}", response_text),
+        ).into())
+    }
+}
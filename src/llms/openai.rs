@@ -1,12 +1,21 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use crate::llms::LlmModel;
+use crate::llms::{Completion, EmbeddingModel, LlmModel, QueryError, TokenUsage};
+use crate::llms::capabilities::ModelCapabilities;
+use crate::llms::tokenizer::Tokenizer;
+use crate::llms::error::{ErrorClass, ProviderError};
+use crate::secret::ApiKey;
+use crate::telemetry;
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
 
 pub struct OpenAiModel {
     client: Client,
-    api_key: String,
+    api_key: ApiKey,
     model: String,
+    base_url: String,
+    organization: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -27,6 +36,7 @@ struct OpenAIRequest {
 #[derive(Deserialize, Debug)]
 struct OpenAIResponseChoice {
     message: OpenAIResponseMessage,
+    finish_reason: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -34,9 +44,16 @@ struct OpenAIResponseMessage {
     content: String,
 }
 
+#[derive(Deserialize, Debug)]
+struct OpenAIUsage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+}
+
 #[derive(Deserialize, Debug)]
 struct OpenAIResponse {
     choices: Vec<OpenAIResponseChoice>,
+    usage: Option<OpenAIUsage>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -53,15 +70,78 @@ struct OpenAIErrorDetails {
     code: String,
 }
 
+#[derive(Deserialize, Debug)]
+struct OpenAIModelList {
+    data: Vec<OpenAIModelEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIModelEntry {
+    id: String,
+}
+
+#[derive(Serialize)]
+struct OpenAIEmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIEmbeddingResponse {
+    data: Vec<OpenAIEmbedding>,
+}
+
 impl OpenAiModel {
-    pub fn new(api_key: String, model: String) -> Self {
+    pub fn new(api_key: ApiKey, model: String) -> Self {
         Self {
             client: Client::new(),
             api_key,
             model,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            organization: None,
         }
     }
 
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_organization(mut self, organization: String) -> Self {
+        self.organization = Some(organization);
+        self
+    }
+
+    /// Ids of the models the API key has access to.
+    pub async fn available_models(&self) -> Result<Vec<String>, QueryError> {
+        let mut request_builder = self.client
+            .get(format!("{}/models", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key.value().await?));
+
+        if let Some(organization) = &self.organization {
+            request_builder = request_builder.header("OpenAI-Organization", organization);
+        }
+
+        let response = request_builder.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ProviderError::new(
+                ErrorClass::from_status(status.as_u16()),
+                format!("OpenAI API returned {}", status),
+            ).into());
+        }
+        let list: OpenAIModelList = response.json().await?;
+        Ok(list.data.into_iter().map(|model| model.id).collect())
+    }
+
     fn get_reasoning_effort(&self) -> Option<String> {
         if self.model == "o3-mini" {
             Some("medium".to_string())
@@ -81,7 +161,36 @@ impl LlmModel for OpenAiModel {
         "OpenAI"
     }
 
-    async fn query(&self, prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::known(&self.model)
+    }
+
+    fn tokenizer(&self) -> Tokenizer {
+        Tokenizer::openai(&self.model)
+    }
+
+    async fn check(&self) -> Option<Result<(), QueryError>> {
+        Some(self.available_models().await.map(|_| ()))
+    }
+
+    #[tracing::instrument(
+        name = "provider_call",
+        skip_all,
+        fields(
+            provider = "OpenAI",
+            model = %self.model,
+            otel.name = %format!("chat {}", self.model),
+            otel.kind = "client",
+            gen_ai.operation.name = "chat",
+            gen_ai.system = "openai",
+            gen_ai.request.model = %self.model,
+            gen_ai.response.model = tracing::field::Empty,
+            gen_ai.usage.input_tokens = tracing::field::Empty,
+            gen_ai.usage.output_tokens = tracing::field::Empty,
+            gen_ai.response.finish_reasons = tracing::field::Empty,
+        ),
+    )]
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
         let request = OpenAIRequest {
             model: self.model.clone(),
             messages: vec![Message {
@@ -92,36 +201,143 @@ impl LlmModel for OpenAiModel {
             reasoning_effort: self.get_reasoning_effort(),
         };
 
-        let response = self.client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
+        let mut request_builder = self.client
+            .post(format!("{}/chat/completions", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key.value().await?))
+            .header("Content-Type", "application/json");
+
+        if let Some(organization) = &self.organization {
+            request_builder = request_builder.header("OpenAI-Organization", organization);
+        }
+
+        let response = request_builder
             .json(&request)
             .send()
             .await?;
 
+        let status = response.status().as_u16();
         let response_text = response.text().await?;
         
         // Try to parse as successful response
         if let Ok(response_data) = serde_json::from_str::<OpenAIResponse>(&response_text) {
-            return Ok(response_data
-                .choices
-                .first()
-                .map(|choice| choice.message.content.clone())
-                .unwrap_or_else(|| String::from("No response generated.")));
+            let choice = response_data.choices.into_iter().next();
+            let finish_reason = choice.as_ref().and_then(|choice| choice.finish_reason.clone());
+            let text = choice
+                .map(|choice| choice.message.content)
+                .unwrap_or_else(|| String::from("No response generated."));
+            let completion = Completion {
+                text,
+                model: self.model.clone(),
+                provider: self.provider().to_string(),
+                usage: response_data.usage.map(|usage| TokenUsage {
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: usage.completion_tokens,
+                }),
+                finish_reason,
+            };
+            telemetry::record_completion(&completion);
+            return Ok(completion);
         }
         
         // Try to parse as error response
         if let Ok(error_data) = serde_json::from_str::<OpenAIError>(&response_text) {
-            return Err(format!(
+            return Err(ProviderError::new(ErrorClass::from_status(status), format!(
                 "OpenAI API Error: {} (Type: {}, Code: {})", 
                 error_data.error.message,
                 error_data.error.r#type,
                 error_data.error.code
-            ).into());
+            )).into());
         }
         
         // If neither parsing worked, return the raw response
-        Err(format!("Unexpected API response: {}", response_text).into())
+        Err(ProviderError::new(
+            ErrorClass::from_status(status),
+            format!("Unexpected API response: {}", response_text),
+        ).into())
+    }
+}
+
+pub struct OpenAiEmbeddingModel {
+    client: Client,
+    api_key: ApiKey,
+    model: String,
+    base_url: String,
+}
+
+impl OpenAiEmbeddingModel {
+    pub fn new(api_key: ApiKey, model: String) -> Self {
+        Self {
+            client: Client::new(),
+            api_key,
+            model,
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
     }
-} 
\ No newline at end of file
+
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for OpenAiEmbeddingModel {
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn provider(&self) -> &str {
+        "OpenAI"
+    }
+
+    #[tracing::instrument(
+        name = "provider_call",
+        skip_all,
+        fields(
+            provider = "OpenAI",
+            model = %self.model,
+            inputs = inputs.len(),
+            otel.name = %format!("embeddings {}", self.model),
+            otel.kind = "client",
+            gen_ai.operation.name = "embeddings",
+            gen_ai.system = "openai",
+            gen_ai.request.model = %self.model,
+        ),
+    )]
+    async fn embed(&self, inputs: &[String], dimensions: Option<u32>) -> Result<Vec<Vec<f32>>, QueryError> {
+        let request = OpenAIEmbeddingRequest {
+            model: &self.model,
+            input: inputs,
+            dimensions,
+        };
+
+        let response = self.client
+            .post(format!("{}/embeddings", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key.value().await?))
+            .json(&request)
+            .send()
+            .await?;
+
+        let status = response.status().as_u16();
+        let response_text = response.text().await?;
+
+        if let Ok(mut response_data) = serde_json::from_str::<OpenAIEmbeddingResponse>(&response_text) {
+            response_data.data.sort_by_key(|embedding| embedding.index);
+            return Ok(response_data.data.into_iter().map(|embedding| embedding.embedding).collect());
+        }
+
+        if let Ok(error_data) = serde_json::from_str::<OpenAIError>(&response_text) {
+            return Err(ProviderError::new(ErrorClass::from_status(status), format!(
+                "OpenAI API Error: {} (Type: {}, Code: {})",
+                error_data.error.message,
+                error_data.error.r#type,
+                error_data.error.code
+            )).into());
+        }
+
+        Err(ProviderError::new(
+            ErrorClass::from_status(status),
+            format!("Unexpected API response: {}", response_text),
+        ).into())
+    }
+}
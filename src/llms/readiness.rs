@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use crate::llms::model_collection::ModelCollection;
+use crate::llms::LlmModel;
+
+/// Outcome of the last upstream check of one model.
+#[derive(Clone)]
+pub struct ProviderCheck {
+    pub model_name: String,
+    pub provider: String,
+    pub result: Result<(), String>,
+    pub checked_at: Instant,
+}
+
+/// Runs the models' upstream checks for the readiness probe and remembers
+/// the results for `ttl`, so frequent probes do not hammer the providers.
+pub struct ReadinessChecker {
+    ttl: Duration,
+    timeout: Duration,
+    results: Mutex<HashMap<String, ProviderCheck>>,
+}
+
+impl ReadinessChecker {
+    pub fn new(ttl: Duration, timeout: Duration) -> Self {
+        Self {
+            ttl,
+            timeout,
+            results: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns one result per model that has something to check, running
+    /// the checks whose cached result has expired concurrently.
+    pub async fn check(&self, models: &ModelCollection) -> Vec<ProviderCheck> {
+        let stale: Vec<(String, Arc<dyn LlmModel>)> = {
+            let results = self.results.lock().unwrap();
+            models
+                .list_models()
+                .into_iter()
+                .filter(|(name, _, _)| {
+                    results
+                        .get(name.as_str())
+                        .is_none_or(|check| check.checked_at.elapsed() >= self.ttl)
+                })
+                .filter_map(|(name, _, _)| Some((name.clone(), models.get_model(name)?)))
+                .collect()
+        };
+
+        let mut tasks = JoinSet::new();
+        for (name, model) in stale {
+            let timeout = self.timeout;
+            tasks.spawn(async move {
+                let result = match tokio::time::timeout(timeout, model.check()).await {
+                    Ok(Some(result)) => result.map_err(|e| e.to_string()),
+                    Ok(None) => return None,
+                    Err(_) => Err(format!("Check timed out after {}s", timeout.as_secs())),
+                };
+                Some(ProviderCheck {
+                    model_name: name,
+                    provider: model.provider().to_string(),
+                    result,
+                    checked_at: Instant::now(),
+                })
+            });
+        }
+
+        let mut fresh = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            if let Ok(Some(check)) = joined {
+                fresh.push(check);
+            }
+        }
+
+        let mut results = self.results.lock().unwrap();
+        for check in fresh {
+            if let Err(e) = &check.result {
+                tracing::warn!(model = %check.model_name, provider = %check.provider, error = %e, "Provider readiness check failed");
+            }
+            results.insert(check.model_name.clone(), check);
+        }
+
+        let mut checks: Vec<ProviderCheck> = results
+            .values()
+            .filter(|check| models.get_model(&check.model_name).is_some())
+            .cloned()
+            .collect();
+        checks.sort_by(|a, b| a.model_name.cmp(&b.model_name));
+        checks
+    }
+}
@@ -0,0 +1,194 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use crate::llms::{Completion, LlmModel, ProviderHealth, QueryError};
+use crate::llms::capabilities::{Capability, ModelCapabilities};
+use crate::llms::tokenizer::Tokenizer;
+use crate::metrics::metrics;
+
+/// What a router model decides on besides the prompt.
+#[derive(Default)]
+pub struct RouteRequest<'a> {
+    pub prompt: &'a str,
+    /// Who sent the request, as told by the `X-Caller` header.
+    pub caller: Option<&'a str>,
+    /// Features the request needs, e.g. `vision` for prompts with images.
+    pub requires: &'a [Capability],
+}
+
+impl<'a> RouteRequest<'a> {
+    pub fn new(prompt: &'a str) -> Self {
+        Self { prompt, ..Self::default() }
+    }
+}
+
+/// Conditions a request must all meet for a rule to apply. A rule without
+/// conditions applies to every request.
+#[derive(Default)]
+pub struct RouteConditions {
+    pub min_tokens: Option<usize>,
+    pub max_tokens: Option<usize>,
+    pub requires: Vec<Capability>,
+    pub callers: Vec<String>,
+    /// Answer the classifier must give, ignoring case.
+    pub verdict: Option<String>,
+}
+
+pub struct RouteRule {
+    pub conditions: RouteConditions,
+    pub target: String,
+    pub model: Arc<dyn LlmModel>,
+}
+
+/// A cheap model asked how to route requests the other conditions cannot
+/// tell apart.
+pub struct Classifier {
+    pub model: Arc<dyn LlmModel>,
+    pub instructions: String,
+    pub max_prompt_chars: usize,
+    pub timeout: Duration,
+}
+
+impl Classifier {
+    /// First word of the classifier's answer in lower case, or `None` when
+    /// it failed.
+    async fn verdict(&self, prompt: &str) -> Option<String> {
+        let excerpt: String = prompt.chars().take(self.max_prompt_chars).collect();
+        let question = format!("{}\n\nRequest:\n{}", self.instructions, excerpt);
+
+        let answer = match tokio::time::timeout(self.timeout, self.model.query(&question)).await {
+            Ok(Ok(completion)) => completion.text,
+            Ok(Err(e)) => {
+                warn!(classifier = self.model.model_name(), error = %e, "Classifier failed, skipping rules that need its verdict");
+                return None;
+            }
+            Err(_) => {
+                warn!(classifier = self.model.model_name(), "Classifier timed out, skipping rules that need its verdict");
+                return None;
+            }
+        };
+
+        let verdict = answer
+            .split_whitespace()
+            .next()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+            .filter(|word| !word.is_empty());
+        debug!(classifier = self.model.model_name(), verdict = ?verdict, "Classified request");
+        verdict
+    }
+}
+
+/// A virtual model handing each request to the model of the first rule it
+/// meets, or to the default model.
+pub struct RouterModel {
+    name: String,
+    targets: String,
+    rules: Vec<RouteRule>,
+    default: (String, Arc<dyn LlmModel>),
+    classifier: Option<Classifier>,
+}
+
+impl RouterModel {
+    pub fn new(name: String, rules: Vec<RouteRule>, default: (String, Arc<dyn LlmModel>), classifier: Option<Classifier>) -> Self {
+        let mut targets: Vec<&str> = Vec::new();
+        for target in rules.iter().map(|rule| rule.target.as_str()).chain([default.0.as_str()]) {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        let targets = targets.join(" | ");
+
+        Self {
+            name,
+            targets,
+            rules,
+            default,
+            classifier,
+        }
+    }
+
+    /// Distinct models requests can be routed to.
+    fn models(&self) -> Vec<&Arc<dyn LlmModel>> {
+        let mut seen: Vec<&str> = Vec::new();
+        let mut models = Vec::new();
+        for (target, model) in self.rules.iter().map(|rule| (&rule.target, &rule.model)).chain([(&self.default.0, &self.default.1)]) {
+            if !seen.contains(&target.as_str()) {
+                seen.push(target);
+                models.push(model);
+            }
+        }
+        models
+    }
+
+    async fn choose(&self, request: &RouteRequest<'_>) -> (String, Arc<dyn LlmModel>) {
+        let tokens = Tokenizer::approximate().count(request.prompt);
+        // The classifier is asked at most once, and only when a rule needs it
+        let mut verdict: Option<Option<String>> = None;
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            let conditions = &rule.conditions;
+            let matches = conditions.min_tokens.is_none_or(|min| tokens >= min)
+                && conditions.max_tokens.is_none_or(|max| tokens <= max)
+                && conditions.requires.iter().all(|capability| request.requires.contains(capability))
+                && (conditions.callers.is_empty() || request.caller.is_some_and(|caller| conditions.callers.iter().any(|c| c == caller)));
+            if !matches {
+                continue;
+            }
+
+            if let Some(expected) = &conditions.verdict {
+                if verdict.is_none() {
+                    verdict = Some(match &self.classifier {
+                        Some(classifier) => classifier.verdict(request.prompt).await,
+                        None => None,
+                    });
+                }
+                if !verdict.iter().flatten().any(|verdict| verdict.eq_ignore_ascii_case(expected)) {
+                    continue;
+                }
+            }
+
+            return self.routed(&(index + 1).to_string(), &rule.target, &rule.model, tokens);
+        }
+
+        self.routed("default", &self.default.0, &self.default.1, tokens)
+    }
+
+    fn routed(&self, rule: &str, target: &str, model: &Arc<dyn LlmModel>, tokens: usize) -> (String, Arc<dyn LlmModel>) {
+        info!(router = %self.name, model = target, rule, tokens, "Routed request");
+        metrics().routes.with_label_values(&[self.name.as_str(), target, rule]).inc();
+        (target.to_string(), model.clone())
+    }
+}
+
+#[async_trait]
+impl LlmModel for RouterModel {
+    fn model_name(&self) -> &str {
+        &self.targets
+    }
+
+    fn provider(&self) -> &str {
+        "Router"
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::common(self.models().into_iter().map(|model| model.capabilities()))
+    }
+
+    fn tokenizer(&self) -> Tokenizer {
+        Tokenizer::common(self.models().into_iter().map(|model| model.tokenizer()))
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.models().into_iter().flat_map(|model| model.health()).collect()
+    }
+
+    async fn route(&self, request: &RouteRequest<'_>) -> Option<(String, Arc<dyn LlmModel>)> {
+        Some(self.choose(request).await)
+    }
+
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
+        let (_, model) = self.choose(&RouteRequest::new(prompt)).await;
+        model.query(prompt).await
+    }
+}
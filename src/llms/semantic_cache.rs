@@ -0,0 +1,302 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use crate::llms::{Completion, EmbeddingModel, QueryError};
+
+#[derive(Serialize, Deserialize, Clone)]
+struct IndexEntry {
+    model: String,
+    vector: Vec<f32>,
+    created_at: u64,
+    completion: Completion,
+}
+
+/// How often expired entries are dropped from memory and the index file
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Answers prompts that are close in meaning to an earlier prompt for the
+/// same model with the earlier answer. Prompts are embedded and compared by
+/// cosine similarity against a brute-force index, optionally persisted as a
+/// JSON lines file holding the same entries as memory.
+pub struct SemanticCache {
+    embedder: Arc<dyn EmbeddingModel>,
+    threshold: f32,
+    ttl: Duration,
+    max_entries: usize,
+    index_path: Option<PathBuf>,
+    entries: Arc<Mutex<VecDeque<IndexEntry>>>,
+    /// Held while changing the entries and the index file together
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl SemanticCache {
+    pub fn new(
+        embedder: Arc<dyn EmbeddingModel>,
+        threshold: f32,
+        ttl: Duration,
+        max_entries: usize,
+        index_path: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            embedder,
+            threshold,
+            ttl,
+            max_entries: max_entries.max(1),
+            index_path,
+            entries: Arc::new(Mutex::new(VecDeque::new())),
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Reads the entries persisted in the index file, if any, and rewrites
+    /// it without the expired and evicted ones.
+    pub async fn load(&self) {
+        let Some(path) = &self.index_path else { return };
+        let Ok(content) = tokio::fs::read_to_string(path).await else { return };
+
+        let _writing = self.writing.lock().await;
+        {
+            let now = now_secs();
+            let mut entries = self.entries.lock().unwrap();
+            for entry in content.lines().filter_map(|line| serde_json::from_str::<IndexEntry>(line).ok()) {
+                if now.saturating_sub(entry.created_at) <= self.ttl.as_secs() {
+                    entries.push_back(entry);
+                }
+            }
+            while entries.len() > self.max_entries {
+                entries.pop_front();
+            }
+        }
+        self.persist().await;
+    }
+
+    /// Drops expired entries every few minutes, so they leave the index
+    /// file without waiting for a restart.
+    pub async fn expire_periodically(self: Arc<Self>) {
+        let mut ticker = tokio::time::interval(EXPIRE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            self.expire().await;
+        }
+    }
+
+    pub async fn expire(&self) {
+        let _writing = self.writing.lock().await;
+        let expired = {
+            let now = now_secs();
+            let mut entries = self.entries.lock().unwrap();
+            let before = entries.len();
+            entries.retain(|entry| now.saturating_sub(entry.created_at) <= self.ttl.as_secs());
+            before - entries.len()
+        };
+        if expired > 0 {
+            tracing::debug!(expired, "Dropped expired semantic cache entries");
+            self.persist().await;
+        }
+    }
+
+    pub fn embedder(&self) -> &dyn EmbeddingModel {
+        self.embedder.as_ref()
+    }
+
+    pub async fn embed(&self, prompt: &str) -> Result<Vec<f32>, QueryError> {
+        self.embedder
+            .embed(&[prompt.to_string()], None)
+            .await?
+            .pop()
+            .ok_or_else(|| "Embedding model returned no vector".into())
+    }
+
+    /// Returns the cached completion most similar to `vector` for `model`,
+    /// together with its similarity, if it reaches the threshold. The index
+    /// is scanned on a blocking thread, as it may hold many vectors.
+    pub async fn find(&self, model: &str, vector: Vec<f32>) -> Option<(Completion, f32)> {
+        let entries = self.entries.clone();
+        let model = model.to_string();
+        let (threshold, ttl) = (self.threshold, self.ttl.as_secs());
+
+        tokio::task::spawn_blocking(move || {
+            let now = now_secs();
+            let entries = entries.lock().unwrap();
+            entries
+                .iter()
+                .filter(|entry| entry.model == model && now.saturating_sub(entry.created_at) <= ttl)
+                .map(|entry| (entry, cosine_similarity(&entry.vector, &vector)))
+                .filter(|(_, similarity)| *similarity >= threshold)
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(entry, similarity)| (entry.completion.clone(), similarity))
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// Adds an entry. A full cache first evicts its oldest tenth, and the
+    /// index file is then rewritten; otherwise the entry is appended to it.
+    pub async fn insert(&self, model: &str, vector: Vec<f32>, completion: &Completion) {
+        let entry = IndexEntry {
+            model: model.to_string(),
+            vector,
+            created_at: now_secs(),
+            completion: completion.clone(),
+        };
+        let line = match &self.index_path {
+            Some(_) => serde_json::to_string(&entry).ok(),
+            None => None,
+        };
+
+        let _writing = self.writing.lock().await;
+        let evicted = {
+            let mut entries = self.entries.lock().unwrap();
+            let evicted = entries.len() >= self.max_entries;
+            if evicted {
+                let count = (self.max_entries / 10).clamp(1, entries.len());
+                entries.drain(..count);
+            }
+            entries.push_back(entry);
+            evicted
+        };
+
+        let Some(path) = &self.index_path else { return };
+        if evicted {
+            self.persist().await;
+            return;
+        }
+
+        let result = async {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let line = line.ok_or("cannot serialize entry")? + "\n";
+            let mut file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+            file.write_all(line.as_bytes()).await?;
+            // Writes complete in the background until flushed
+            file.flush().await?;
+            Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+        }.await;
+
+        if let Err(e) = result {
+            tracing::warn!(path = %path.display(), error = %e, "Failed to write semantic cache index");
+        }
+    }
+
+    /// Rewrites the index file with the entries in memory. Callers hold
+    /// `writing`.
+    async fn persist(&self) {
+        let Some(path) = self.index_path.clone() else { return };
+        let entries = self.entries.clone();
+
+        let result = tokio::task::spawn_blocking(move || write_index(&path, &entries)).await;
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!(error = %e, "Failed to rewrite semantic cache index"),
+            Err(e) => tracing::warn!(error = %e, "Failed to rewrite semantic cache index"),
+        }
+    }
+}
+
+/// Writes the entries to a temporary file next to `path` and moves it into
+/// place, so a crash never leaves a partial index.
+fn write_index(path: &Path, entries: &Mutex<VecDeque<IndexEntry>>) -> std::io::Result<()> {
+    let content: String = entries
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|entry| serde_json::to_string(entry).ok())
+        .map(|line| line + "\n")
+        .collect();
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let temporary = path.with_extension("jsonl.tmp");
+    std::fs::write(&temporary, content)?;
+    std::fs::rename(&temporary, path)
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llms::local_embedding::HashingEmbeddingModel;
+
+    fn completion(text: &str) -> Completion {
+        Completion {
+            text: text.to_string(),
+            model: "model".to_string(),
+            provider: "provider".to_string(),
+            usage: None,
+            finish_reason: None,
+        }
+    }
+
+    fn index_lines(path: &Path) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[tokio::test]
+    async fn index_file_holds_at_most_max_entries() {
+        let directory = std::env::temp_dir().join(format!("llmapi-semantic-{}", std::process::id()));
+        let path = directory.join("index.jsonl");
+        let cache = SemanticCache::new(Arc::new(HashingEmbeddingModel::new(8)), 0.99, Duration::from_secs(60), 20, Some(path.clone()));
+
+        for i in 0..20 {
+            cache.insert("model", vec![i as f32, 1.0], &completion(&i.to_string())).await;
+        }
+        assert_eq!(index_lines(&path), 20);
+
+        // The oldest two are evicted and the file rewritten
+        cache.insert("model", vec![20.0, 1.0], &completion("20")).await;
+        assert_eq!(index_lines(&path), 19);
+        assert!(cache.find("model", vec![0.0, 1.0]).await.is_none());
+        assert_eq!(cache.find("model", vec![20.0, 1.0]).await.unwrap().0.text, "20");
+        assert!(cache.find("other", vec![20.0, 1.0]).await.is_none());
+
+        let reloaded = SemanticCache::new(Arc::new(HashingEmbeddingModel::new(8)), 0.99, Duration::from_secs(60), 20, Some(path.clone()));
+        reloaded.load().await;
+        assert_eq!(reloaded.entries.lock().unwrap().len(), 19);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_entries_leave_the_index_file() {
+        let directory = std::env::temp_dir().join(format!("llmapi-semantic-expire-{}", std::process::id()));
+        let path = directory.join("index.jsonl");
+        let cache = SemanticCache::new(Arc::new(HashingEmbeddingModel::new(8)), 0.99, Duration::from_secs(60), 10, Some(path.clone()));
+
+        cache.insert("model", vec![1.0, 0.0], &completion("old")).await;
+        cache.insert("model", vec![0.0, 1.0], &completion("new")).await;
+        cache.entries.lock().unwrap()[0].created_at -= 120;
+
+        assert!(cache.find("model", vec![1.0, 0.0]).await.is_none());
+        cache.expire().await;
+        assert_eq!(index_lines(&path), 1);
+        assert_eq!(cache.find("model", vec![0.0, 1.0]).await.unwrap().0.text, "new");
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
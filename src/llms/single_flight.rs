@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use crate::llms::{Completion, QueryError};
+use crate::llms::error::{ErrorClass, ProviderError};
+
+/// Failure shared with waiting requests. The original error cannot be cloned,
+/// so waiters receive its class and message.
+#[derive(Clone)]
+struct SharedFailure {
+    class: ErrorClass,
+    message: String,
+}
+
+type SharedResult = Result<Completion, SharedFailure>;
+
+/// Removes the in-flight entry when the leading request finishes or is
+/// dropped. Dropping the sender wakes waiters; if no result was sent, the
+/// first of them to wake leads the request and the others wait for it.
+struct FlightGuard<'a> {
+    inflight: &'a Mutex<HashMap<String, broadcast::Sender<SharedResult>>>,
+    key: &'a str,
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.inflight.lock().unwrap().remove(self.key);
+    }
+}
+
+/// Coalesces identical concurrent requests so only one of them reaches the
+/// provider and every caller receives its result.
+#[derive(Default)]
+pub struct SingleFlight {
+    inflight: Mutex<HashMap<String, broadcast::Sender<SharedResult>>>,
+}
+
+impl SingleFlight {
+    /// Runs `query`, or waits for the identical request already running.
+    /// Also returns whether the result was shared by another request, whose
+    /// usage is then already accounted for.
+    pub async fn run<F, Fut>(&self, key: &str, query: F) -> (Result<Completion, QueryError>, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Completion, QueryError>>,
+    {
+        loop {
+            let waiting = {
+                let mut inflight = self.inflight.lock().unwrap();
+                match inflight.get(key) {
+                    Some(sender) => Some(sender.subscribe()),
+                    None => {
+                        let (sender, _) = broadcast::channel(1);
+                        inflight.insert(key.to_string(), sender);
+                        None
+                    }
+                }
+            };
+
+            let Some(mut receiver) = waiting else { break };
+            match receiver.recv().await {
+                Ok(Ok(completion)) => return (Ok(completion), true),
+                Ok(Err(failure)) => return (Err(ProviderError::new(failure.class, failure.message).into()), true),
+                // The leading request was cancelled before it finished, so
+                // a new leader is chosen
+                Err(_) => continue,
+            }
+        }
+
+        let guard = FlightGuard {
+            inflight: &self.inflight,
+            key,
+        };
+        let result = query().await;
+
+        let shared = match &result {
+            Ok(completion) => Ok(completion.clone()),
+            Err(e) => Err(SharedFailure {
+                class: ErrorClass::of(e.as_ref()),
+                message: e.to_string(),
+            }),
+        };
+        if let Some(sender) = self.inflight.lock().unwrap().get(key) {
+            let _ = sender.send(shared);
+        }
+        drop(guard);
+
+        (result, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::Notify;
+
+    fn completion(text: &str) -> Completion {
+        Completion {
+            text: text.to_string(),
+            model: "model".to_string(),
+            provider: "provider".to_string(),
+            usage: None,
+            finish_reason: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn waiters_share_the_leaders_error() {
+        let single_flight = SingleFlight::default();
+        let calls = AtomicUsize::new(0);
+        let release = Notify::new();
+        let query = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            release.notified().await;
+            Err::<Completion, QueryError>(ProviderError::new(ErrorClass::RateLimited, "slow down").into())
+        };
+
+        let (leader, first, second, _) = tokio::join!(
+            single_flight.run("key", query),
+            single_flight.run("key", query),
+            single_flight.run("key", query),
+            async { release.notify_one() },
+        );
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!leader.1);
+        for (result, shared) in [first, second] {
+            assert!(shared);
+            let error = result.unwrap_err();
+            assert_eq!(ErrorClass::of(error.as_ref()), ErrorClass::RateLimited);
+            assert_eq!(error.to_string(), "slow down");
+        }
+        assert!(single_flight.inflight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn one_waiter_takes_over_when_the_leader_is_cancelled() {
+        let single_flight = SingleFlight::default();
+        let never = Notify::new();
+        let calls = AtomicUsize::new(0);
+        let query = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            Ok(completion("waiter"))
+        };
+
+        let leader = single_flight.run("key", || async {
+            never.notified().await;
+            Ok(completion("leader"))
+        });
+
+        let (_, first, second, third) = tokio::join!(
+            // Gives up on the leader once the others are waiting for it
+            tokio::time::timeout(std::time::Duration::from_millis(10), leader),
+            single_flight.run("key", query),
+            single_flight.run("key", query),
+            single_flight.run("key", query),
+        );
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let results = [first, second, third];
+        assert_eq!(results.iter().filter(|(_, shared)| !shared).count(), 1);
+        for (result, _) in results {
+            assert_eq!(result.unwrap().text, "waiter");
+        }
+        assert!(single_flight.inflight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn different_keys_run_separately() {
+        let single_flight = SingleFlight::default();
+        let (a, b) = tokio::join!(
+            single_flight.run("a", || async { Ok(completion("a")) }),
+            single_flight.run("b", || async { Ok(completion("b")) }),
+        );
+        assert_eq!((a.0.unwrap().text, a.1), ("a".to_string(), false));
+        assert_eq!((b.0.unwrap().text, b.1), ("b".to_string(), false));
+    }
+}
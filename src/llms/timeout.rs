@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::llms::{Completion, LlmModel, ProviderHealth, QueryError};
+use crate::llms::capabilities::ModelCapabilities;
+use crate::llms::tokenizer::Tokenizer;
+use crate::llms::error::{ErrorClass, ProviderError};
+
+/// Gives up on a provider call after `timeout`, failing it with the
+/// `timeout` error class. Placed inside the circuit breaker, so timeouts
+/// count as failures there and fallback models move on after them.
+pub struct TimeoutModel {
+    inner: Arc<dyn LlmModel>,
+    timeout: Duration,
+}
+
+impl TimeoutModel {
+    pub fn new(inner: Arc<dyn LlmModel>, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+pub fn timeout_error(timeout: Duration) -> QueryError {
+    ProviderError::new(ErrorClass::Timeout, format!("Upstream call timed out after {} ms", timeout.as_millis())).into()
+}
+
+#[async_trait]
+impl LlmModel for TimeoutModel {
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    fn provider(&self) -> &str {
+        self.inner.provider()
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn tokenizer(&self) -> Tokenizer {
+        self.inner.tokenizer()
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.inner.health()
+    }
+
+    async fn check(&self) -> Option<Result<(), QueryError>> {
+        self.inner.check().await
+    }
+
+    async fn query(&self, prompt: &str) -> Result<Completion, QueryError> {
+        tokio::time::timeout(self.timeout, self.inner.query(prompt))
+            .await
+            .unwrap_or_else(|_| Err(timeout_error(self.timeout)))
+    }
+}
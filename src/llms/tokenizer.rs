@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use tiktoken_rs::CoreBPE;
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer as Encoding};
+use crate::llms::LlmModel;
+
+/// What to do with prompts longer than the model's context window allows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowAction {
+    /// Fail the request without calling upstream.
+    #[default]
+    Reject,
+    /// Drop the start of the prompt, keeping the end where the question
+    /// usually is.
+    Truncate,
+    /// Send the prompt anyway and let the provider decide.
+    Allow,
+}
+
+/// Counts tokens the way a model's provider does: exactly for OpenAI models
+/// with their tiktoken encoding, approximately with `cl100k_base` for others.
+#[derive(Clone, Copy)]
+pub struct Tokenizer {
+    bpe: &'static CoreBPE,
+    encoding: &'static str,
+    exact: bool,
+}
+
+impl Tokenizer {
+    /// The encoding of an OpenAI model, or the approximation for models
+    /// tiktoken does not know.
+    pub fn openai(model: &str) -> Self {
+        let (bpe, encoding) = match get_tokenizer(model) {
+            Some(Encoding::O200kBase) => (tiktoken_rs::o200k_base_singleton(), "o200k_base"),
+            Some(Encoding::Cl100kBase) => (tiktoken_rs::cl100k_base_singleton(), "cl100k_base"),
+            Some(Encoding::P50kBase) => (tiktoken_rs::p50k_base_singleton(), "p50k_base"),
+            Some(Encoding::P50kEdit) => (tiktoken_rs::p50k_edit_singleton(), "p50k_edit"),
+            Some(Encoding::R50kBase | Encoding::Gpt2) => (tiktoken_rs::r50k_base_singleton(), "r50k_base"),
+            None => return Self::approximate(),
+        };
+        Self { bpe, encoding, exact: true }
+    }
+
+    /// The tokenizer for models whose encoding is not known.
+    pub fn approximate() -> Self {
+        Self {
+            bpe: tiktoken_rs::cl100k_base_singleton(),
+            encoding: "cl100k_base",
+            exact: false,
+        }
+    }
+
+    /// One tokenizer for whichever of `tokenizers` is used: exact only when
+    /// all of them are and share the encoding.
+    pub fn common(tokenizers: impl IntoIterator<Item = Self>) -> Self {
+        let mut tokenizers = tokenizers.into_iter();
+        let Some(first) = tokenizers.next() else {
+            return Self::approximate();
+        };
+        if first.exact && tokenizers.all(|tokenizer| tokenizer.exact && tokenizer.encoding == first.encoding) {
+            first
+        } else {
+            Self::approximate()
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        self.encoding
+    }
+
+    /// Whether counts match what the provider bills.
+    pub fn is_exact(&self) -> bool {
+        self.exact
+    }
+
+    /// Token IDs of `text`. Special tokens such as `<|endoftext|>` count as
+    /// plain text, as providers treat them in prompts.
+    pub fn encode(&self, text: &str) -> Vec<u32> {
+        self.bpe.encode_ordinary(text)
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.encode(text).len()
+    }
+
+    /// The end of `text` that fits in `max_tokens`, or `None` when not even
+    /// its last character does.
+    fn keep_last<'a>(&self, text: &'a str, max_tokens: usize) -> Option<Cow<'a, str>> {
+        let tokens = self.encode(text);
+        if tokens.len() <= max_tokens {
+            return Some(Cow::Borrowed(text));
+        }
+        // A cut inside a multi-byte character cannot be decoded, so the cut
+        // moves forward until it can
+        (tokens.len() - max_tokens..tokens.len())
+            .find_map(|start| self.bpe.decode(tokens[start..].to_vec()).ok())
+            .filter(|kept| !kept.is_empty())
+            .map(Cow::Owned)
+    }
+}
+
+/// Context window and tokens kept free for the answer: `reserve_output_tokens`,
+/// but no more than the model writes. `None` when the context window is
+/// unknown.
+fn budget(model: &dyn LlmModel, reserve_output_tokens: u32) -> Option<(u32, u32)> {
+    let capabilities = model.capabilities();
+    let reserved = capabilities.max_output_tokens.map_or(reserve_output_tokens, |max| max.min(reserve_output_tokens));
+    Some((capabilities.context_window?, reserved))
+}
+
+/// Most prompt tokens `model` takes with `reserve_output_tokens` left for
+/// the answer, if its context window is known.
+pub fn max_prompt_tokens(model: &dyn LlmModel, reserve_output_tokens: u32) -> Option<usize> {
+    budget(model, reserve_output_tokens).map(|(context_window, reserved)| context_window.saturating_sub(reserved) as usize)
+}
+
+/// Checks `prompt` against the context window of `model`, leaving room for
+/// `reserve_output_tokens` of answer. Returns the prompt to send, shortened
+/// for `Truncate`, or why it was rejected. Prompts for models with an
+/// unknown context window pass unchecked.
+pub fn fit_prompt<'a>(
+    prompt: &'a str,
+    model_name: &str,
+    model: &dyn LlmModel,
+    on_overflow: OverflowAction,
+    reserve_output_tokens: u32,
+) -> Result<Cow<'a, str>, String> {
+    let Some((context_window, reserved)) = budget(model, reserve_output_tokens).filter(|_| on_overflow != OverflowAction::Allow) else {
+        return Ok(Cow::Borrowed(prompt));
+    };
+    let limit = context_window.saturating_sub(reserved) as usize;
+
+    let tokenizer = model.tokenizer();
+    let tokens = tokenizer.count(prompt);
+    if tokens <= limit {
+        return Ok(Cow::Borrowed(prompt));
+    }
+
+    let kept = match on_overflow {
+        OverflowAction::Truncate => tokenizer.keep_last(prompt, limit),
+        _ => None,
+    };
+    match kept {
+        Some(kept) => {
+            tracing::warn!(model = model_name, tokens, limit, "Prompt exceeds the context window, dropping its start");
+            Ok(kept)
+        }
+        None => Err(format!(
+            "Prompt has {}{} tokens, more than the {} that fit model '{}' (context window {}, {} reserved for the answer)",
+            if tokenizer.is_exact() { "" } else { "about " },
+            tokens,
+            limit,
+            model_name,
+            context_window,
+            reserved,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_last_keeps_the_end_or_nothing() {
+        let tokenizer = Tokenizer::openai("gpt-4o");
+        assert_eq!(tokenizer.keep_last("hello world", 5).as_deref(), Some("hello world"));
+        assert_eq!(tokenizer.keep_last("hello world", 1).as_deref(), Some(" world"));
+        assert_eq!(tokenizer.keep_last("hello world", 0), None);
+    }
+}
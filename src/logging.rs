@@ -0,0 +1,113 @@
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_appender::non_blocking::WorkerGuard;
+use std::fmt;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+use crate::config::{LogFormat, LoggingConfig, TelemetryConfig};
+use crate::telemetry;
+
+/// Keeps log output and span export running. Dropping it flushes buffered
+/// log lines and exports the remaining spans.
+pub struct LogGuard {
+    _writer: WorkerGuard,
+    tracer_provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.tracer_provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to flush trace export: {}", e);
+            }
+        }
+    }
+}
+
+/// Sets up the global tracing subscriber. `RUST_LOG`, when set, takes
+/// precedence over the configured level. Log lines are written on a
+/// background thread; the returned guard flushes them when dropped and has to
+/// be kept alive for as long as the process logs.
+pub fn init(config: &LoggingConfig, telemetry_config: &TelemetryConfig) -> Result<LogGuard, Box<dyn std::error::Error>> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.level))?;
+
+    let (writer, writer_guard) = match &config.file {
+        Some(path) => {
+            let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+            let directory = directory.map(|parent| parent.to_path_buf()).unwrap_or_else(|| ".".into());
+            let file_name = path.file_name().ok_or("Log file path has no file name")?;
+            std::fs::create_dir_all(&directory)?;
+            tracing_appender::non_blocking(tracing_appender::rolling::never(directory, file_name))
+        }
+        None => tracing_appender::non_blocking(std::io::stderr()),
+    };
+
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(false);
+    let fmt_layer = match config.format {
+        LogFormat::Text => fmt_layer.boxed(),
+        LogFormat::Json => fmt_layer.json().with_current_span(true).with_span_list(true).boxed(),
+        LogFormat::Journal => fmt_layer.event_format(JournalFormat).boxed(),
+    };
+
+    let tracer_provider = if telemetry_config.enabled {
+        Some(telemetry::tracer_provider(telemetry_config)?)
+    } else {
+        None
+    };
+    let otel_layer = tracer_provider.as_ref().map(telemetry::layer);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otel_layer)
+        .try_init()
+        .map_err(|e| e.to_string())?;
+
+    Ok(LogGuard {
+        _writer: writer_guard,
+        tracer_provider,
+    })
+}
+
+/// Formats events as `<priority>span{fields}: message fields` without
+/// timestamps, which journald adds itself. The `<N>` prefix is the syslog
+/// priority journald reads from stderr lines.
+struct JournalFormat;
+
+impl<S, N> FormatEvent<S, N> for JournalFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let priority = match *event.metadata().level() {
+            Level::ERROR => 3,
+            Level::WARN => 4,
+            Level::INFO => 6,
+            Level::DEBUG | Level::TRACE => 7,
+        };
+        write!(writer, "<{}>", priority)?;
+
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                write!(writer, "{}", span.name())?;
+                if let Some(fields) = span.extensions().get::<FormattedFields<N>>() {
+                    if !fields.is_empty() {
+                        write!(writer, "{{{}}}", fields)?;
+                    }
+                }
+                write!(writer, ": ")?;
+            }
+        }
+
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
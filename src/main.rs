@@ -1,58 +1,172 @@
 mod llms;
 mod modes;
 mod config;
+mod metrics;
+mod logging;
+mod telemetry;
+mod shutdown;
+mod reload;
+mod secret;
+mod server;
 
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use llms::discovery::Catalog;
 use llms::model_collection::ModelCollection;
-use config::Config;
+use config::{Config, TlsConfig};
+#[cfg(unix)]
+use config::LogFormat;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
-    /// Mode to run in (chat or api)
-    #[arg(value_enum)]
+    /// Mode to run in
+    #[command(subcommand)]
     mode: Mode,
 
     /// Set the port number for the API server
-    #[arg(long = "port")]
+    #[arg(long = "port", global = true)]
     port: Option<u16>,
 
-    /// Set the port number in config file
-    #[arg(long = "set-port")]
+    /// Address or host name for the API server to listen on, e.g. `::` or `localhost`
+    #[arg(long = "bind", global = true)]
+    bind: Option<String>,
+
+    /// Listen on a Unix domain socket instead of an address and port
+    #[arg(long = "unix-socket", global = true)]
+    unix_socket: Option<PathBuf>,
+
+    /// Serve HTTPS with this PEM certificate chain
+    #[arg(long = "tls-cert", global = true, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// Private key for `--tls-cert`
+    #[arg(long = "tls-key", global = true, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Set the port number in config file (same as `config set port <PORT>`)
+    #[arg(long = "set-port", global = true)]
     set_port: Option<u16>,
+
+    /// Config file to use instead of the default location
+    #[arg(long = "config", global = true, env = "LLMAPI_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Profile from the config file to apply over the base settings
+    #[arg(long = "profile", global = true, env = "LLMAPI_PROFILE")]
+    profile: Option<String>,
 }
 
-#[derive(Clone, ValueEnum)]
+#[derive(Subcommand)]
 enum Mode {
+    /// Start interactive chat session
     Chat,
+    /// Start API server
     Api,
+    /// Show, check or change the configuration
+    Config {
+        #[command(subcommand)]
+        command: modes::config_command::ConfigCommand,
+    },
+    /// Run as Windows service
     #[cfg(windows)]
     Service,
+    /// Run API server as a systemd service
+    #[cfg(unix)]
+    Serve {
+        /// Write the process ID to this file
+        #[arg(long)]
+        pidfile: Option<PathBuf>,
+    },
+    /// Write a systemd unit file for `serve`
+    #[cfg(unix)]
+    InstallSystemd {
+        /// Where to write the unit, `-` for stdout
+        #[arg(long, default_value = modes::serve::DEFAULT_UNIT_PATH)]
+        path: PathBuf,
+        /// User the service runs as
+        #[arg(long)]
+        user: Option<String>,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
-    
+async fn main() {
+    if let Err(e) = run(Cli::parse()).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = &cli.config {
+        Config::use_path(path.clone());
+    }
+    if let Some(profile) = &cli.profile {
+        Config::use_profile(profile.clone());
+    }
+
     // Handle port configuration if specified
     if let Some(port) = cli.set_port {
-        let config = Config { port };
-        config.save()?;
+        modes::config_command::set("port", &port.to_string())?;
         println!("Port configuration saved. API will now use port {}", port);
         return Ok(());
     }
-    
-    let models = ModelCollection::new();
-    let config = Config::load();
-    
+
+    if let Mode::Config { command } = &cli.mode {
+        return modes::config_command::run(command);
+    }
+
+    let mut config = Config::load()?;
+
+    #[cfg(unix)]
+    if let Mode::InstallSystemd { path, user } = &cli.mode {
+        return modes::serve::install_systemd(path, user.as_deref(), &config);
+    }
+
+    // The service has no console, so it logs next to the executable by default
+    #[cfg(windows)]
+    if matches!(cli.mode, Mode::Service) && config.logging.file.is_none() {
+        config.logging.file = Some(modes::service::default_log_path());
+    }
+    // journald timestamps stderr lines itself and reads priority prefixes
+    #[cfg(unix)]
+    if matches!(cli.mode, Mode::Serve { .. })
+        && config.logging.file.is_none()
+        && matches!(config.logging.format, LogFormat::Text)
+        && std::env::var_os("JOURNAL_STREAM").is_some()
+    {
+        config.logging.format = LogFormat::Journal;
+    }
+    let _log_guard = logging::init(&config.logging, &config.telemetry)?;
+
     // Use CLI port if specified, otherwise use config port
-    let port = cli.port.unwrap_or(config.port);
+    if let Some(port) = cli.port {
+        config.port = port;
+    }
+    if let Some(bind) = cli.bind {
+        config.bind_address = bind;
+    }
+    if let Some(path) = cli.unix_socket {
+        config.unix_socket = Some(path);
+    }
+    if let (Some(cert), Some(key)) = (cli.tls_cert, cli.tls_key) {
+        config.tls = Some(TlsConfig { cert, key, watch: true });
+    }
     
     match cli.mode {
-        Mode::Chat => modes::chat::run(models).await?,
-        Mode::Api => modes::api::run(models, port).await?,
+        Mode::Chat => {
+            let catalog = Catalog::fetch(&config, &Catalog::default()).await;
+            modes::chat::run(ModelCollection::new(&config, &catalog, None), config.prompt_limits.clone()).await?
+        }
+        Mode::Api => modes::api::run(config, shutdown::signal()).await?,
+        Mode::Config { .. } => unreachable!("handled before the config is loaded"),
         #[cfg(windows)]
-        Mode::Service => modes::service::run(port)?,
+        Mode::Service => modes::service::run(config)?,
+        #[cfg(unix)]
+        Mode::Serve { pidfile } => modes::serve::run(config, pidfile).await?,
+        #[cfg(unix)]
+        Mode::InstallSystemd { .. } => unreachable!("handled before logging is set up"),
     }
     
     Ok(())
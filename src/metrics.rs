@@ -0,0 +1,132 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::OnceLock;
+
+/// Process-wide Prometheus metrics, served by the API at `GET /metrics`.
+pub struct Metrics {
+    registry: Registry,
+    /// Requests by endpoint, model alias, provider and outcome (`ok`,
+    /// `error` or `cancelled`).
+    pub requests: IntCounterVec,
+    /// Request latency by endpoint, model alias and provider.
+    pub request_duration: HistogramVec,
+    /// Tokens reported by providers, by model alias, provider and kind
+    /// (`prompt` or `completion`).
+    pub tokens: IntCounterVec,
+    /// Failed requests by model alias, provider and error class.
+    pub errors: IntCounterVec,
+    /// Repeated upstream attempts by model alias, provider and kind (`provider`
+    /// for retries inside a provider, `fallback` and `backend` for moving on
+    /// to another model or backend).
+    pub retries: IntCounterVec,
+    /// Cache lookups by model alias, cache (`exact` or `semantic`) and
+    /// result (`hit` or `miss`).
+    pub cache_lookups: IntCounterVec,
+    /// Requests currently waiting for an answer, by model alias.
+    pub inflight: IntGaugeVec,
+    /// Requests router models handed on, by router, chosen model and rule
+    /// (its position, or `default`).
+    pub routes: IntCounterVec,
+    /// Requests answered by an identical request's upstream call, by model
+    /// alias.
+    pub coalesced: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let requests = IntCounterVec::new(
+            Opts::new("llmapi_requests_total", "Requests handled by the API"),
+            &["endpoint", "model", "provider", "status"],
+        ).unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new("llmapi_request_duration_seconds", "Time taken to answer API requests")
+                .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0]),
+            &["endpoint", "model", "provider"],
+        ).unwrap();
+        let tokens = IntCounterVec::new(
+            Opts::new("llmapi_tokens_total", "Tokens reported by providers"),
+            &["model", "provider", "kind"],
+        ).unwrap();
+        let errors = IntCounterVec::new(
+            Opts::new("llmapi_errors_total", "Failed requests by error class"),
+            &["model", "provider", "error_type"],
+        ).unwrap();
+        let retries = IntCounterVec::new(
+            Opts::new("llmapi_retries_total", "Repeated upstream attempts"),
+            &["model", "provider", "kind"],
+        ).unwrap();
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("llmapi_cache_lookups_total", "Response cache lookups"),
+            &["model", "cache", "result"],
+        ).unwrap();
+        let inflight = IntGaugeVec::new(
+            Opts::new("llmapi_inflight_requests", "Requests currently waiting for an answer"),
+            &["model"],
+        ).unwrap();
+        let routes = IntCounterVec::new(
+            Opts::new("llmapi_routed_requests_total", "Requests handed on by router models"),
+            &["router", "model", "rule"],
+        ).unwrap();
+        let coalesced = IntCounterVec::new(
+            Opts::new("llmapi_coalesced_requests_total", "Requests sharing an identical request's upstream call"),
+            &["model"],
+        ).unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(request_duration.clone())).unwrap();
+        registry.register(Box::new(tokens.clone())).unwrap();
+        registry.register(Box::new(errors.clone())).unwrap();
+        registry.register(Box::new(retries.clone())).unwrap();
+        registry.register(Box::new(cache_lookups.clone())).unwrap();
+        registry.register(Box::new(inflight.clone())).unwrap();
+        registry.register(Box::new(routes.clone())).unwrap();
+        registry.register(Box::new(coalesced.clone())).unwrap();
+
+        Self {
+            registry,
+            requests,
+            request_duration,
+            tokens,
+            errors,
+            retries,
+            cache_lookups,
+            inflight,
+            routes,
+            coalesced,
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!(error = %e, "Failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Counts a request as in flight for as long as the guard lives.
+pub struct InflightGuard(prometheus::IntGauge);
+
+impl InflightGuard {
+    pub fn new(model: &str) -> Self {
+        let gauge = metrics().inflight.with_label_values(&[model]);
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
@@ -1,80 +1,1299 @@
 use axum::{
-    routing::{post, get},
-    Router,
     Json,
-    extract::State,
+    extract::{DefaultBodyLimit, Path, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+use std::borrow::Cow;
+use std::sync::{Arc, OnceLock};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{info, info_span, warn, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+use crate::config::{Config, PromptLimitsConfig, TimeoutConfig};
+use crate::llms::{Completion, LlmModel};
+use crate::metrics::{metrics, InflightGuard};
+use crate::llms::cache::ResponseCache;
+use crate::llms::capabilities::Capability;
+use crate::llms::circuit_breaker::CircuitState;
+use crate::llms::error::ErrorClass;
+use crate::llms::semantic_cache::SemanticCache;
+use crate::llms::single_flight::SingleFlight;
+use crate::llms::readiness::ReadinessChecker;
+use crate::llms::discovery::Catalog;
 use crate::llms::model_collection::ModelCollection;
+use crate::llms::router::RouteRequest;
+use crate::llms::timeout::timeout_error;
+use crate::reload::{self, ModelRegistry};
+use crate::secret::ApiKey;
+use crate::server::{self, Listener, Tls};
+use crate::telemetry;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(rename_all = "PascalCase")]
 pub struct QueryRequest {
-    #[serde(alias = "model_name")]
-    pub model_name: String,
+    /// A model's name or alias; `default_model` when left out.
+    #[serde(default, alias = "model_name")]
+    pub model_name: Option<String>,
     #[serde(alias = "prompt")]
     pub prompt: String,
+    #[serde(default, alias = "no_cache")]
+    pub no_cache: bool,
+    #[serde(default, alias = "no_dedup")]
+    pub no_dedup: bool,
+    /// Overrides the configured upstream timeout, up to `timeouts.max_ms`.
+    #[serde(default, alias = "timeout_ms")]
+    pub timeout_ms: Option<u64>,
+    /// Features the request needs, e.g. `vision` when the prompt refers to
+    /// images. Router models can route by them.
+    #[serde(default, alias = "requires")]
+    pub requires: Vec<Capability>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct QueryResponse {
     #[serde(rename = "Response")]
     pub response: String,
+    #[serde(rename = "Model", skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(rename = "Provider", skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(rename = "Cached")]
+    pub cached: bool,
+    #[serde(rename = "CacheSimilarity", skip_serializing_if = "Option::is_none")]
+    pub cache_similarity: Option<f32>,
+    /// Whether the start of the prompt was dropped to fit the model's
+    /// context window.
+    #[serde(rename = "PromptTruncated", skip_serializing_if = "std::ops::Not::not")]
+    pub prompt_truncated: bool,
+}
+
+impl QueryResponse {
+    fn completed(completion: Completion, cached: bool) -> Self {
+        Self {
+            response: completion.text,
+            model: Some(completion.model),
+            provider: Some(completion.provider),
+            cached,
+            cache_similarity: None,
+            prompt_truncated: false,
+        }
+    }
+
+    fn truncated(mut self, prompt_truncated: bool) -> Self {
+        self.prompt_truncated = prompt_truncated;
+        self
+    }
+
+    fn failed(message: String) -> Self {
+        Self {
+            response: message,
+            model: None,
+            provider: None,
+            cached: false,
+            cache_similarity: None,
+            prompt_truncated: false,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Batch(Vec<String>),
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmbeddingRequest {
+    #[serde(alias = "model_name")]
+    pub model_name: String,
+    #[serde(alias = "input")]
+    pub input: EmbeddingInput,
+    #[serde(default, alias = "dimensions")]
+    pub dimensions: Option<u32>,
+    #[serde(default, alias = "timeout_ms")]
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EmbeddingData {
+    #[serde(rename = "Index")]
+    pub index: usize,
+    #[serde(rename = "Embedding")]
+    pub embedding: Vec<f32>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct EmbeddingResponse {
+    #[serde(rename = "Data")]
+    pub data: Vec<EmbeddingData>,
+    #[serde(rename = "Model")]
+    pub model: String,
+    #[serde(rename = "Provider")]
+    pub provider: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "PascalCase")]
+pub struct TokenizeRequest {
+    /// A model's name or alias; `default_model` when left out.
+    #[serde(default, alias = "model_name")]
+    pub model_name: Option<String>,
+    #[serde(alias = "prompt")]
+    pub prompt: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TokenizeResponse {
+    #[serde(rename = "ModelName")]
+    pub model_name: String,
+    /// The tiktoken encoding the tokens come from.
+    #[serde(rename = "Encoding")]
+    pub encoding: String,
+    /// `false` when the model's own tokenizer is not known and the count is
+    /// an approximation.
+    #[serde(rename = "Exact")]
+    pub exact: bool,
+    #[serde(rename = "Count")]
+    pub count: usize,
+    #[serde(rename = "Tokens")]
+    pub tokens: Vec<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CountTokensResponse {
+    #[serde(rename = "ModelName")]
+    pub model_name: String,
+    #[serde(rename = "Encoding")]
+    pub encoding: String,
+    #[serde(rename = "Exact")]
+    pub exact: bool,
+    #[serde(rename = "Count")]
+    pub count: usize,
+    #[serde(rename = "ContextWindow", skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u32>,
+    /// Context window less the tokens reserved for the answer.
+    #[serde(rename = "MaxPromptTokens", skip_serializing_if = "Option::is_none")]
+    pub max_prompt_tokens: Option<usize>,
+    /// Whether the prompt fits in `MaxPromptTokens`. Left out when the
+    /// context window is unknown.
+    #[serde(rename = "FitsContext", skip_serializing_if = "Option::is_none")]
+    pub fits_context: Option<bool>,
+}
+
+/// Error returned with a non-success status, in the shape documented in the
+/// README: `{"error": {"message": ..., "type": ...}}`.
+pub struct ApiError {
+    pub status: StatusCode,
+    pub error_type: String,
+    pub message: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ApiErrorBody {
+    error: ApiErrorDetails,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ApiErrorDetails {
+    message: String,
+    r#type: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, error_type: &str, message: String) -> Self {
+        Self {
+            status,
+            error_type: error_type.to_string(),
+            message,
+        }
+    }
+
+    /// Maps a provider failure to the status a client should see.
+    pub fn from_provider(error: &(dyn std::error::Error + 'static)) -> Self {
+        let class = ErrorClass::of(error);
+        let status = match class {
+            ErrorClass::InvalidRequest => StatusCode::BAD_REQUEST,
+            ErrorClass::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ErrorClass::Overloaded | ErrorClass::CircuitOpen => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorClass::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        Self::new(status, &class.to_string(), error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ApiErrorBody {
+            error: ApiErrorDetails {
+                message: self.message,
+                r#type: self.error_type,
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// A registered model and what is known about its limits, features and
+/// prices. Unknown values are left out.
+#[derive(Serialize, ToSchema)]
 pub struct ModelInfo {
     #[serde(rename = "ModelName")]
     pub model_name: String,
     #[serde(rename = "Provider")]
     pub provider: String,
+    /// The provider's model, or the models behind a balanced, fallback or
+    /// router model.
+    #[serde(rename = "Model")]
+    pub model: String,
+    #[serde(rename = "Aliases", skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Whether `/query` uses this model when `ModelName` is left out.
+    #[serde(rename = "Default", skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+    #[serde(rename = "ContextWindow", skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<u32>,
+    #[serde(rename = "MaxOutputTokens", skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(rename = "SupportsVision", skip_serializing_if = "Option::is_none")]
+    pub supports_vision: Option<bool>,
+    #[serde(rename = "SupportsTools", skip_serializing_if = "Option::is_none")]
+    pub supports_tools: Option<bool>,
+    #[serde(rename = "SupportsStreaming", skip_serializing_if = "Option::is_none")]
+    pub supports_streaming: Option<bool>,
+    #[serde(rename = "SupportsJson", skip_serializing_if = "Option::is_none")]
+    pub supports_json: Option<bool>,
+    #[serde(rename = "Pricing", skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<ModelPricing>,
+    /// Day the provider retires the model, as `YYYY-MM-DD`.
+    #[serde(rename = "DeprecationDate", skip_serializing_if = "Option::is_none")]
+    pub deprecation_date: Option<String>,
 }
 
-pub async fn run(models: ModelCollection, port: u16) -> Result<(), Box<dyn std::error::Error>> {
-    let models = Arc::new(models);
-    
-    let router = Router::new()
-        .route("/query", post(handle_query))
-        .route("/models", get(handle_list_models))
-        .with_state(models);
-
-    let address = format!("0.0.0.0:{}", port).parse::<std::net::SocketAddr>()?;
-    println!("Starting API server on http://localhost:{}", port);
-    axum::serve(
-        tokio::net::TcpListener::bind(address).await?, 
-        router
-    ).await?;
-    
+/// Price in US dollars per million tokens.
+#[derive(Serialize, ToSchema)]
+pub struct ModelPricing {
+    #[serde(rename = "InputPerMillion")]
+    pub input_per_million: f64,
+    #[serde(rename = "OutputPerMillion")]
+    pub output_per_million: f64,
+}
+
+impl ModelInfo {
+    fn new(models: &ModelCollection, name: &str, model: &dyn LlmModel) -> Self {
+        let capabilities = model.capabilities();
+        Self {
+            model_name: name.to_string(),
+            provider: model.provider().to_string(),
+            model: model.model_name().to_string(),
+            aliases: models.aliases_of(name),
+            default: models.is_default(name),
+            context_window: capabilities.context_window,
+            max_output_tokens: capabilities.max_output_tokens,
+            supports_vision: capabilities.supports_vision,
+            supports_tools: capabilities.supports_tools,
+            supports_streaming: capabilities.supports_streaming,
+            supports_json: capabilities.supports_json,
+            pricing: capabilities.pricing.map(|pricing| ModelPricing {
+                input_per_million: pricing.input_per_million,
+                output_per_million: pricing.output_per_million,
+            }),
+            deprecation_date: capabilities.deprecation_date,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BackendHealth {
+    #[serde(rename = "Provider")]
+    pub provider: String,
+    #[serde(rename = "Model")]
+    pub model: String,
+    #[serde(rename = "State")]
+    pub state: CircuitState,
+    #[serde(rename = "FailureRate")]
+    pub failure_rate: f64,
+    #[serde(rename = "RetryInSecs", skip_serializing_if = "Option::is_none")]
+    pub retry_in_secs: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ModelHealth {
+    #[serde(rename = "ModelName")]
+    pub model_name: String,
+    #[serde(rename = "Backends")]
+    pub backends: Vec<BackendHealth>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LivenessResponse {
+    #[serde(rename = "Status")]
+    pub status: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessCheck {
+    #[serde(rename = "Name")]
+    pub name: &'static str,
+    #[serde(rename = "Ok")]
+    pub ok: bool,
+    #[serde(rename = "Detail")]
+    pub detail: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProviderReadiness {
+    #[serde(rename = "ModelName")]
+    pub model_name: String,
+    #[serde(rename = "Provider")]
+    pub provider: String,
+    #[serde(rename = "Ok")]
+    pub ok: bool,
+    #[serde(rename = "Error", skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(rename = "CheckedSecsAgo")]
+    pub checked_secs_ago: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    #[serde(rename = "Status")]
+    pub status: &'static str,
+    #[serde(rename = "Checks")]
+    pub checks: Vec<ReadinessCheck>,
+    #[serde(rename = "Providers", skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<ProviderReadiness>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReloadResponse {
+    #[serde(rename = "Status")]
+    pub status: &'static str,
+    #[serde(rename = "Models")]
+    pub models: usize,
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub registry: Arc<ModelRegistry>,
+    pub cache: Option<Arc<ResponseCache>>,
+    pub semantic_cache: Option<Arc<SemanticCache>>,
+    pub single_flight: Option<Arc<SingleFlight>>,
+    pub readiness: Option<Arc<ReadinessChecker>>,
+    pub timeouts: Arc<TimeoutConfig>,
+    pub prompt_limits: Arc<PromptLimitsConfig>,
+    /// Token the `/admin` endpoints require; they are disabled without one.
+    pub admin_token: Option<ApiKey>,
+}
+
+/// Binds the configured address or socket and serves the API until
+/// `shutdown` completes. The models are reloaded on SIGHUP and, if enabled,
+/// when the config file changes.
+pub async fn run(
+    config: Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listeners = server::bind(&config).await?;
+    info!("Starting API server");
+
+    let registry = start_registry(&config).await;
+    if config.watch_config {
+        tokio::spawn(reload::watch_config_file(registry.clone(), CONFIG_POLL_INTERVAL));
+    }
+    #[cfg(unix)]
+    tokio::spawn(reload::reload_on_hangup(registry.clone()));
+
+    serve(listeners, registry, config, shutdown).await
+}
+
+/// Builds the models, asking the providers for theirs first when discovery
+/// is enabled, and keeps the discovered models up to date.
+pub async fn start_registry(config: &Config) -> Arc<ModelRegistry> {
+    let catalog = Catalog::fetch(config, &Catalog::default()).await;
+    let registry = Arc::new(ModelRegistry::new(config, catalog));
+    tokio::spawn(reload::refresh_discovered_models(registry.clone()));
+    registry
+}
+
+/// How often the config file is checked for changes.
+pub const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Serves the API on `listeners` until `shutdown` completes, then stops
+/// accepting connections and gives in-flight requests up to the configured
+/// shutdown timeout to finish.
+pub async fn serve(
+    listeners: Vec<Listener>,
+    registry: Arc<ModelRegistry>,
+    config: Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let drain_timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    let state = AppState {
+        registry,
+        cache: config.cache.build().map(Arc::new),
+        semantic_cache: config.semantic_cache.build(&config.secrets).map(Arc::new),
+        single_flight: config.deduplicate_requests.then(|| Arc::new(SingleFlight::default())),
+        readiness: config.readiness.build().map(Arc::new),
+        timeouts: Arc::new(config.timeouts.clone()),
+        prompt_limits: Arc::new(config.prompt_limits.clone()),
+        admin_token: config.admin.token.as_ref().map(|token| config.secrets.key(token)),
+    };
+
+    if let Some(cache) = &state.cache {
+        tokio::spawn(cache.clone().sweep_periodically());
+    }
+    if let Some(semantic_cache) = &state.semantic_cache {
+        semantic_cache.load().await;
+        tokio::spawn(semantic_cache.clone().expire_periodically());
+        let embedder = semantic_cache.embedder();
+        info!(provider = embedder.provider(), model = embedder.model_name(), "Semantic cache enabled");
+    }
+
+    let (router, _) = api_routes().split_for_parts();
+    let router = router
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(middleware::from_fn(request_context))
+        .with_state(state);
+    // Outermost, so preflight requests are answered before routing
+    let router = match config.cors.build() {
+        Some(cors) => router.layer(cors),
+        None => router,
+    };
+
+    let tls = config.tls.as_ref().map(Tls::load).transpose()?;
+    if let (Some(tls), Some(tls_config)) = (&tls, &config.tls) {
+        if tls_config.watch {
+            tokio::spawn(tls.clone().watch(tls_config.clone(), CONFIG_POLL_INTERVAL));
+        }
+    }
+
+    let (stop_tx, stop_rx) = watch::channel(false);
+    let mut server = tokio::spawn(server::serve(listeners, tls, router, stop_rx));
+
+    tokio::select! {
+        result = &mut server => return Ok(result?),
+        _ = shutdown => {}
+    }
+
+    info!(timeout_secs = drain_timeout.as_secs(), "Shutting down, waiting for in-flight requests");
+    let _ = stop_tx.send(true);
+    match tokio::time::timeout(drain_timeout, &mut server).await {
+        Ok(result) => result?,
+        Err(_) => {
+            warn!("In-flight requests did not finish before the shutdown timeout, aborting them");
+            server.abort();
+        }
+    }
+    info!("API server stopped");
+
     Ok(())
 }
 
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "llmapi-rust", description = "Gateway serving OpenAI, Anthropic and Ollama models behind one API."),
+    tags(
+        (name = "models", description = "Querying models"),
+        (name = "health", description = "Health, readiness and metrics"),
+        (name = "admin", description = "Operating the server"),
+        (name = "docs", description = "This documentation"),
+    ),
+    modifiers(&AdminTokenScheme),
+)]
+struct ApiDoc;
+
+/// Declares the bearer token the admin endpoints require.
+struct AdminTokenScheme;
+
+impl utoipa::Modify for AdminTokenScheme {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+
+        openapi.components.get_or_insert_with(Default::default).add_security_scheme(
+            "admin_token",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+/// Every route of the API. Routes are registered together with their
+/// OpenAPI description, so the document cannot miss one.
+fn api_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(handle_query))
+        .routes(routes!(handle_list_models))
+        .routes(routes!(handle_get_model))
+        .routes(routes!(handle_embeddings))
+        .routes(routes!(handle_tokenize))
+        .routes(routes!(handle_count_tokens))
+        .routes(routes!(handle_provider_health))
+        .routes(routes!(handle_metrics))
+        .routes(routes!(handle_liveness))
+        .routes(routes!(handle_readiness))
+        .routes(routes!(handle_reload))
+        .routes(routes!(handle_openapi))
+        .routes(routes!(handle_docs))
+}
+
+/// The OpenAPI document served at `/openapi.json`.
+pub fn openapi() -> &'static utoipa::openapi::OpenApi {
+    static OPENAPI: OnceLock<utoipa::openapi::OpenApi> = OnceLock::new();
+    OPENAPI.get_or_init(|| api_routes().into_openapi())
+}
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Runs each request in a span carrying its request ID, which is taken from
+/// the `X-Request-Id` header or generated, and echoed in the response.
+async fn request_context(request: Request, next: Next) -> Response {
+    let request_id = request.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        otel.name = %format!("{} {}", request.method(), request.uri().path()),
+        otel.kind = "server",
+        http.request.method = %request.method(),
+        url.path = %request.uri().path(),
+        http.response.status_code = tracing::field::Empty,
+    );
+    // Continue the caller's trace when it sent a W3C `traceparent` header
+    let _ = span.set_parent(telemetry::extract_context(request.headers()));
+
+    async move {
+        let started = Instant::now();
+        let mut response = next.run(request).await;
+        Span::current().record("http.response.status_code", response.status().as_u16());
+        info!(
+            status = response.status().as_u16(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Request finished"
+        );
+
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        response
+    }
+    .instrument(span)
+    .await
+}
+
+const CALLER_HEADER: &str = "x-caller";
+
+/// Send a prompt to a model
+///
+/// Failures are answered with status 200 as well, with the error message in
+/// `Response` and without `Model` and `Provider`, except for unknown models,
+/// which are answered with 404 and similar names. Requests for a router
+/// model are answered by the model its rules choose.
+#[utoipa::path(
+    post,
+    path = "/query",
+    tag = "models",
+    request_body = QueryRequest,
+    params(
+        ("X-Caller" = Option<String>, Header, description = "Who sends the request, for router rules with `callers`. Not authenticated."),
+    ),
+    responses(
+        (status = 200, description = "The model's answer or the error", body = QueryResponse),
+        (status = 404, description = "Unknown model, with similar names suggested", body = ApiErrorBody),
+        (status = 413, description = "The body is larger than `max_body_bytes`", body = String, content_type = "text/plain"),
+        (status = 422, description = "The body is not a valid request", body = String, content_type = "text/plain"),
+    ),
+)]
 async fn handle_query(
-    State(models): State<Arc<ModelCollection>>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<QueryRequest>,
-) -> Json<QueryResponse> {
-    match models.get_model(&request.model_name) {
-        Some(model) => {
-            let response = model.query(&request.prompt).await
-                .unwrap_or_else(|e| format!("Error: {}", e));
-            Json(QueryResponse { response })
-        }
-        None => Json(QueryResponse { 
-            response: format!("Model '{}' not found", request.model_name) 
-        })
+) -> Result<Json<QueryResponse>, ApiError> {
+    // Unknown models are not recorded in metrics, so arbitrary names cannot
+    // create new label values
+    let models = state.registry.models();
+    let (mut model_name, mut model) = match requested_model(&models, request.model_name.as_deref()) {
+        Ok(found) => found,
+        Err(error) if error.status == StatusCode::NOT_FOUND => return Err(error),
+        Err(error) => return Ok(Json(QueryResponse::failed(error.message))),
+    };
+
+    if request.timeout_ms == Some(0) {
+        return Ok(Json(QueryResponse::failed("TimeoutMs must be greater than 0".to_string())));
+    }
+
+    // Routers are resolved before anything else, so caching, metrics and
+    // timeouts apply to the model that answers. This means a classifier is
+    // asked even when the answer then comes from the cache. Routers only
+    // refer to models defined before them, so this ends.
+    let route = RouteRequest {
+        prompt: &request.prompt,
+        caller: headers.get(CALLER_HEADER).and_then(|value| value.to_str().ok()),
+        requires: &request.requires,
+    };
+    while let Some((target_name, target)) = model.route(&route).await {
+        (model_name, model) = (target_name, target);
+    }
+
+    // Prompts that cannot fit are turned away before they cost anything
+    let prompt = match state.prompt_limits.fit(&request.prompt, &model_name, model.as_ref()) {
+        Ok(prompt) => prompt,
+        Err(message) => return Ok(Json(QueryResponse::failed(message))),
+    };
+    let prompt_truncated = matches!(prompt, Cow::Owned(_));
+
+    let started = Instant::now();
+    let _inflight = InflightGuard::new(&model_name);
+    let disconnect = DisconnectGuard::new("query", &model_name, model.provider());
+
+    let cache = state.cache.as_ref().filter(|_| !request.no_cache);
+    let cache_key = ResponseCache::key(&model_name, model.model_name(), &prompt);
+
+    if let Some(cache) = cache {
+        let cached = cache.get(&cache_key).await;
+        record_cache_lookup(&model_name, "exact", cached.is_some());
+        if let Some(completion) = cached {
+            disconnect.answered();
+            record_completion("query", &model_name, &completion, true, started);
+            return Ok(Json(QueryResponse::completed(completion, true).truncated(prompt_truncated)));
+        }
+    }
+
+    // Similar earlier prompts are only looked up after an exact match failed
+    let semantic_cache = state.semantic_cache.as_ref().filter(|_| !request.no_cache);
+    let semantic_model = format!("{}\n{}", model_name, model.model_name());
+    let mut prompt_vector = None;
+
+    if let Some(semantic_cache) = semantic_cache {
+        match semantic_cache.embed(&prompt).await {
+            Ok(vector) => {
+                let found = semantic_cache.find(&semantic_model, vector.clone()).await;
+                record_cache_lookup(&model_name, "semantic", found.is_some());
+                if let Some((completion, similarity)) = found {
+                    disconnect.answered();
+                    record_completion("query", &model_name, &completion, true, started);
+                    let mut response = QueryResponse::completed(completion, true).truncated(prompt_truncated);
+                    response.cache_similarity = Some(similarity);
+                    return Ok(Json(response));
+                }
+                prompt_vector = Some(vector);
+            }
+            Err(e) => warn!(error = %e, "Failed to embed prompt for semantic cache"),
+        }
+    }
+
+    let query = || async {
+        let result = model.query(&prompt).await;
+        if let Ok(completion) = &result {
+            if let Some(cache) = cache {
+                cache.put(&cache_key, completion).await;
+            }
+            if let (Some(semantic_cache), Some(vector)) = (semantic_cache, prompt_vector) {
+                semantic_cache.insert(&semantic_model, vector, completion).await;
+            }
+        }
+        result
+    };
+
+    // The models time out each provider call themselves; this only bounds
+    // the request as a whole
+    let timeout = state.timeouts.deadline(request.timeout_ms);
+    let upstream = async {
+        match state.single_flight.as_ref().filter(|_| !request.no_dedup) {
+            Some(single_flight) => single_flight.run(&cache_key, query).await,
+            None => (query().await, false),
+        }
+    };
+    let (result, shared) = tokio::time::timeout(timeout, upstream)
+        .await
+        .unwrap_or_else(|_| (Err(timeout_error(timeout)), false));
+    disconnect.answered();
+    if shared {
+        metrics().coalesced.with_label_values(&[&model_name]).inc();
+    }
+
+    match result {
+        Ok(completion) => {
+            // Tokens of a shared call are counted once, by the request that made it
+            record_completion("query", &model_name, &completion, shared, started);
+            Ok(Json(QueryResponse::completed(completion, false).truncated(prompt_truncated)))
+        }
+        Err(e) => {
+            record_failure("query", &model_name, model.provider(), &ErrorClass::of(e.as_ref()).to_string(), started);
+            Ok(Json(QueryResponse::failed(format!("Error: {}", e))))
+        }
+    }
+}
+
+/// The model a request names, or the default model when it names none.
+fn requested_model(models: &ModelCollection, name: Option<&str>) -> Result<(String, Arc<dyn LlmModel>), ApiError> {
+    match name.filter(|name| !name.is_empty()) {
+        Some(name) => models.find_model(name).ok_or_else(|| ApiError::new(
+            StatusCode::NOT_FOUND,
+            "not_found",
+            models.model_not_found(name),
+        )),
+        None => models.default_model().ok_or_else(|| ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_request",
+            "ModelName is required, no default_model is configured".to_string(),
+        )),
+    }
+}
+
+/// Records requests whose client went away before the answer was ready.
+/// The server then drops the handler, which cancels the upstream call it
+/// was waiting for, and with it this guard.
+struct DisconnectGuard<'a> {
+    endpoint: &'static str,
+    model: &'a str,
+    provider: &'a str,
+    started: Instant,
+    answered: bool,
+}
+
+impl<'a> DisconnectGuard<'a> {
+    fn new(endpoint: &'static str, model: &'a str, provider: &'a str) -> Self {
+        Self { endpoint, model, provider, started: Instant::now(), answered: false }
+    }
+
+    fn answered(mut self) {
+        self.answered = true;
+    }
+}
+
+impl Drop for DisconnectGuard<'_> {
+    fn drop(&mut self) {
+        if self.answered {
+            return;
+        }
+        info!(
+            model = self.model,
+            elapsed_ms = self.started.elapsed().as_millis() as u64,
+            "Client disconnected, upstream call cancelled"
+        );
+        let metrics = metrics();
+        metrics.requests.with_label_values(&[self.endpoint, self.model, self.provider, "cancelled"]).inc();
+        metrics.request_duration.with_label_values(&[self.endpoint, self.model, self.provider]).observe(self.started.elapsed().as_secs_f64());
+    }
+}
+
+fn record_cache_lookup(model: &str, cache: &str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    metrics().cache_lookups.with_label_values(&[model, cache, result]).inc();
+}
+
+/// Records a successful request. Tokens are only counted for fresh
+/// completions, as cached ones and those shared with an identical request
+/// did not cost any more.
+fn record_completion(endpoint: &str, model: &str, completion: &Completion, cached: bool, started: Instant) {
+    let provider = completion.provider.as_str();
+    record_success(endpoint, model, provider, started);
+
+    if let (false, Some(usage)) = (cached, completion.usage) {
+        let metrics = metrics();
+        metrics.tokens.with_label_values(&[model, provider, "prompt"]).inc_by(usage.prompt_tokens as u64);
+        metrics.tokens.with_label_values(&[model, provider, "completion"]).inc_by(usage.completion_tokens as u64);
+    }
+}
+
+fn record_success(endpoint: &str, model: &str, provider: &str, started: Instant) {
+    let metrics = metrics();
+    metrics.requests.with_label_values(&[endpoint, model, provider, "ok"]).inc();
+    metrics.request_duration.with_label_values(&[endpoint, model, provider]).observe(started.elapsed().as_secs_f64());
+}
+
+fn record_failure(endpoint: &str, model: &str, provider: &str, error_type: &str, started: Instant) {
+    let metrics = metrics();
+    metrics.requests.with_label_values(&[endpoint, model, provider, "error"]).inc();
+    metrics.request_duration.with_label_values(&[endpoint, model, provider]).observe(started.elapsed().as_secs_f64());
+    metrics.errors.with_label_values(&[model, provider, error_type]).inc();
+}
+
+/// Turn texts into embedding vectors
+#[utoipa::path(
+    post,
+    path = "/embeddings",
+    tag = "models",
+    request_body = EmbeddingRequest,
+    responses(
+        (status = 200, description = "One vector per input, in input order", body = EmbeddingResponse),
+        (status = 400, description = "No input or an invalid `TimeoutMs`", body = ApiErrorBody),
+        (status = 404, description = "Unknown embedding model", body = ApiErrorBody),
+        (status = 413, description = "The body is larger than `max_body_bytes`", body = String, content_type = "text/plain"),
+        (status = 422, description = "The body is not a valid request", body = String, content_type = "text/plain"),
+        (status = 429, description = "The provider's rate limit was hit", body = ApiErrorBody),
+        (status = 502, description = "The provider failed", body = ApiErrorBody),
+        (status = 503, description = "The provider is overloaded or its circuit is open", body = ApiErrorBody),
+        (status = 504, description = "The provider did not answer in time", body = ApiErrorBody),
+    ),
+)]
+async fn handle_embeddings(
+    State(state): State<AppState>,
+    Json(request): Json<EmbeddingRequest>,
+) -> Result<Json<EmbeddingResponse>, ApiError> {
+    let models = state.registry.models();
+    let (model_name, model) = models.find_embedding_model(&request.model_name).ok_or_else(|| ApiError::new(
+        StatusCode::NOT_FOUND,
+        "not_found",
+        models.embedding_model_not_found(&request.model_name),
+    ))?;
+
+    let inputs = match request.input {
+        EmbeddingInput::Single(input) => vec![input],
+        EmbeddingInput::Batch(inputs) => inputs,
+    };
+    if inputs.is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "invalid_request", "Input must not be empty".to_string()));
+    }
+
+    if request.timeout_ms == Some(0) {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "invalid_request", "TimeoutMs must be greater than 0".to_string()));
     }
+
+    let started = Instant::now();
+    let _inflight = InflightGuard::new(&model_name);
+    let disconnect = DisconnectGuard::new("embeddings", &model_name, model.provider());
+    let timeout = state.timeouts.for_request(&model_name, request.timeout_ms);
+    let result = tokio::time::timeout(timeout, model.embed(&inputs, request.dimensions))
+        .await
+        .unwrap_or_else(|_| Err(timeout_error(timeout)));
+    disconnect.answered();
+
+    let embeddings = match result {
+        Ok(embeddings) => embeddings,
+        Err(e) => {
+            let error = ApiError::from_provider(e.as_ref());
+            record_failure("embeddings", &model_name, model.provider(), &error.error_type, started);
+            return Err(error);
+        }
+    };
+
+    record_success("embeddings", &model_name, model.provider(), started);
+
+    Ok(Json(EmbeddingResponse {
+        data: embeddings
+            .into_iter()
+            .enumerate()
+            .map(|(index, embedding)| EmbeddingData { index, embedding })
+            .collect(),
+        model: model.model_name().to_string(),
+        provider: model.provider().to_string(),
+    }))
 }
 
+/// Split a prompt into the tokens a model sees
+///
+/// Counts are exact for OpenAI models. For other models the prompt is split
+/// with `cl100k_base` and `Exact` is `false`.
+#[utoipa::path(
+    post,
+    path = "/tokenize",
+    tag = "models",
+    request_body = TokenizeRequest,
+    responses(
+        (status = 200, description = "The prompt's tokens", body = TokenizeResponse),
+        (status = 400, description = "No `ModelName` and no `default_model`", body = ApiErrorBody),
+        (status = 404, description = "Unknown model, with similar names suggested", body = ApiErrorBody),
+        (status = 413, description = "The body is larger than `max_body_bytes`", body = String, content_type = "text/plain"),
+        (status = 422, description = "The body is not a valid request", body = String, content_type = "text/plain"),
+    ),
+)]
+async fn handle_tokenize(
+    State(state): State<AppState>,
+    Json(request): Json<TokenizeRequest>,
+) -> Result<Json<TokenizeResponse>, ApiError> {
+    let (model_name, model) = requested_model(&state.registry.models(), request.model_name.as_deref())?;
+    let tokenizer = model.tokenizer();
+    let tokens = off_worker(move || tokenizer.encode(&request.prompt)).await?;
+
+    Ok(Json(TokenizeResponse {
+        model_name,
+        encoding: tokenizer.encoding().to_string(),
+        exact: tokenizer.is_exact(),
+        count: tokens.len(),
+        tokens,
+    }))
+}
+
+/// Count a prompt's tokens and check it against the context window
+///
+/// Router models are not resolved; their count is approximate and their
+/// context window the smallest of the models they route to.
+#[utoipa::path(
+    post,
+    path = "/count_tokens",
+    tag = "models",
+    request_body = TokenizeRequest,
+    responses(
+        (status = 200, description = "The prompt's token count", body = CountTokensResponse),
+        (status = 400, description = "No `ModelName` and no `default_model`", body = ApiErrorBody),
+        (status = 404, description = "Unknown model, with similar names suggested", body = ApiErrorBody),
+        (status = 413, description = "The body is larger than `max_body_bytes`", body = String, content_type = "text/plain"),
+        (status = 422, description = "The body is not a valid request", body = String, content_type = "text/plain"),
+    ),
+)]
+async fn handle_count_tokens(
+    State(state): State<AppState>,
+    Json(request): Json<TokenizeRequest>,
+) -> Result<Json<CountTokensResponse>, ApiError> {
+    let (model_name, model) = requested_model(&state.registry.models(), request.model_name.as_deref())?;
+    let tokenizer = model.tokenizer();
+    let count = off_worker(move || tokenizer.count(&request.prompt)).await?;
+    let max_prompt_tokens = state.prompt_limits.max_prompt_tokens(model.as_ref());
+
+    Ok(Json(CountTokensResponse {
+        model_name,
+        encoding: tokenizer.encoding().to_string(),
+        exact: tokenizer.is_exact(),
+        count,
+        context_window: model.capabilities().context_window,
+        max_prompt_tokens,
+        fits_context: max_prompt_tokens.map(|max| count <= max),
+    }))
+}
+
+/// Runs `work` on a blocking thread. Encoding prompts of up to
+/// `max_body_bytes` takes long enough to hold up other requests otherwise.
+async fn off_worker<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, "other", format!("Tokenizing failed: {}", e)))
+}
+
+/// List the available models
+#[utoipa::path(
+    get,
+    path = "/models",
+    tag = "models",
+    responses((status = 200, description = "Registered models, by name", body = Vec<ModelInfo>)),
+)]
 async fn handle_list_models(
-    State(models): State<Arc<ModelCollection>>,
+    State(state): State<AppState>,
 ) -> Json<Vec<ModelInfo>> {
-    let model_list = models.list_models()
+    let models = state.registry.models();
+    let mut model_list: Vec<ModelInfo> = models.list_models()
         .into_iter()
-        .map(|(name, provider, _)| ModelInfo {
-            model_name: name.clone(),
-            provider: provider.to_string(),
-        })
+        .filter_map(|(name, _, _)| models.get_model(name).map(|model| ModelInfo::new(&models, name, model.as_ref())))
         .collect();
+    model_list.sort_by(|a, b| a.model_name.cmp(&b.model_name));
     
     Json(model_list)
-}
\ No newline at end of file
+}
+
+/// Show one model
+#[utoipa::path(
+    get,
+    path = "/models/{name}",
+    tag = "models",
+    params(("name" = String, Path, description = "The model's name or one of its aliases, in any case")),
+    responses(
+        (status = 200, description = "The model", body = ModelInfo),
+        (status = 404, description = "Unknown model, with similar names suggested", body = ApiErrorBody),
+    ),
+)]
+async fn handle_get_model(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<ModelInfo>, ApiError> {
+    let models = state.registry.models();
+    let (name, model) = models.find_model(&name).ok_or_else(|| ApiError::new(
+        StatusCode::NOT_FOUND,
+        "not_found",
+        models.model_not_found(&name),
+    ))?;
+    Ok(Json(ModelInfo::new(&models, &name, model.as_ref())))
+}
+
+/// Circuit breaker and balancing state of every model's backends
+#[utoipa::path(
+    get,
+    path = "/health/providers",
+    tag = "health",
+    responses((status = 200, description = "Backends by model", body = Vec<ModelHealth>)),
+)]
+async fn handle_provider_health(
+    State(state): State<AppState>,
+) -> Json<Vec<ModelHealth>> {
+    let health = state.registry.models().health()
+        .into_iter()
+        .map(|(name, backends)| ModelHealth {
+            model_name: name.clone(),
+            backends: backends
+                .into_iter()
+                .map(|backend| BackendHealth {
+                    provider: backend.provider,
+                    model: backend.model,
+                    state: backend.state,
+                    failure_rate: backend.failure_rate,
+                    retry_in_secs: backend.retry_in_secs,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Json(health)
+}
+
+/// Liveness: answers as long as the process serves requests
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, description = "The server is running", body = LivenessResponse)),
+)]
+async fn handle_liveness() -> Json<LivenessResponse> {
+    Json(LivenessResponse { status: "ok" })
+}
+
+/// Readiness: models are registered and providers reachable
+///
+/// Ready when the last config reload succeeded, models are registered and,
+/// if provider checks are enabled, at least one provider passes its check.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready", body = ReadinessResponse),
+        (status = 503, description = "Not ready", body = ReadinessResponse),
+    ),
+)]
+async fn handle_readiness(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let config_path = Config::config_path();
+    let reload_errors = state.registry.reload_errors();
+    // The problems themselves are only logged, as this endpoint is public
+    let config_detail = if !reload_errors.is_empty() {
+        format!(
+            "Reloading {} failed with {} problem{}, see the log; serving the previous config",
+            config_path.display(),
+            reload_errors.len(),
+            if reload_errors.len() == 1 { "" } else { "s" },
+        )
+    } else if config_path.exists() {
+        format!("Loaded from {}", config_path.display())
+    } else {
+        "No config file, using defaults".to_string()
+    };
+
+    let models = state.registry.models();
+    let model_count = models.list_models().len();
+    let mut checks = vec![
+        ReadinessCheck { name: "config", ok: reload_errors.is_empty(), detail: config_detail },
+        ReadinessCheck {
+            name: "models",
+            ok: model_count > 0,
+            detail: format!("{} models registered", model_count),
+        },
+    ];
+
+    let providers: Vec<ProviderReadiness> = match &state.readiness {
+        Some(readiness) => readiness.check(&models).await
+            .into_iter()
+            .map(|check| ProviderReadiness {
+                model_name: check.model_name,
+                provider: check.provider,
+                ok: check.result.is_ok(),
+                error: check.result.err(),
+                checked_secs_ago: check.checked_at.elapsed().as_secs(),
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    if state.readiness.is_some() {
+        let passed = providers.iter().filter(|provider| provider.ok).count();
+        checks.push(ReadinessCheck {
+            name: "providers",
+            ok: passed > 0 || providers.is_empty(),
+            detail: format!("{} of {} providers passed", passed, providers.len()),
+        });
+    }
+
+    let ready = checks.iter().all(|check| check.ok);
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(ReadinessResponse {
+        status: if ready { "ready" } else { "not_ready" },
+        checks,
+        providers,
+    }))
+}
+
+/// Checks the `Authorization: Bearer` header against `admin.token`.
+async fn authorize_admin(state: &AppState, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(token) = &state.admin_token else {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "forbidden",
+            "Admin endpoints are disabled, set admin.token to enable them".to_string(),
+        ));
+    };
+    let expected = token.value().await.map_err(|e| {
+        warn!(error = %e, "Cannot read the admin token");
+        ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "other", "Cannot read the admin token".to_string())
+    })?;
+
+    let given = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or("");
+    // Compares every byte, so the time taken does not tell how much matched
+    let matches = given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0;
+    if !matches {
+        return Err(ApiError::new(StatusCode::UNAUTHORIZED, "unauthorized", "Missing or wrong admin token".to_string()));
+    }
+    Ok(())
+}
+
+/// Reload the models from the config file
+///
+/// Reloads the config file and swaps in the new models. A config that fails
+/// to load or validate is reported and not applied. Needs the `admin.token`
+/// as a bearer token.
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    responses(
+        (status = 200, description = "The new models are in use", body = ReloadResponse),
+        (status = 401, description = "The admin token is missing or wrong", body = ApiErrorBody),
+        (status = 403, description = "No admin token is configured", body = ApiErrorBody),
+        (status = 422, description = "The config is invalid, the current models stay in use", body = ApiErrorBody),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn handle_reload(State(state): State<AppState>, headers: HeaderMap) -> Result<Json<ReloadResponse>, ApiError> {
+    authorize_admin(&state, &headers).await?;
+
+    match state.registry.reload_blocking("admin endpoint").await {
+        Ok(models) => Ok(Json(ReloadResponse { status: "reloaded", models })),
+        Err(errors) => Err(ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_config", errors.join("; "))),
+    }
+}
+
+/// Prometheus metrics
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses((status = 200, description = "Metrics in the Prometheus text format", body = String, content_type = "text/plain")),
+)]
+async fn handle_metrics() -> impl IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().render(),
+    )
+}
+
+/// This OpenAPI document
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "docs",
+    responses((status = 200, description = "OpenAPI 3 document of the API", content_type = "application/json")),
+)]
+async fn handle_openapi() -> Json<&'static utoipa::openapi::OpenApi> {
+    Json(openapi())
+}
+
+/// Interactive API documentation
+#[utoipa::path(
+    get,
+    path = "/docs",
+    tag = "docs",
+    responses((status = 200, description = "Page rendering `/openapi.json`, with forms to try each endpoint", body = String, content_type = "text/html")),
+)]
+async fn handle_docs() -> impl IntoResponse {
+    // The page is self-contained; it may only talk to this server
+    (
+        [(
+            axum::http::header::CONTENT_SECURITY_POLICY,
+            "default-src 'none'; script-src 'unsafe-inline'; style-src 'unsafe-inline'; connect-src 'self'",
+        )],
+        Html(include_str!("docs.html")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Method};
+    use std::collections::BTreeSet;
+    use tower::ServiceExt;
+
+    /// Every documented path is served, with exactly the documented
+    /// methods. Asking a path for a method it does not serve answers with
+    /// the methods it does, without running any handler.
+    #[tokio::test]
+    async fn openapi_matches_router() {
+        let state = AppState {
+            registry: Arc::new(ModelRegistry::new(&Config::default(), Catalog::default())),
+            cache: None,
+            semantic_cache: None,
+            single_flight: None,
+            readiness: None,
+            timeouts: Arc::new(TimeoutConfig::default()),
+            prompt_limits: Arc::new(PromptLimitsConfig::default()),
+            admin_token: None,
+        };
+        let (router, _) = api_routes().split_for_parts();
+        let router = router.with_state(state);
+
+        let spec = serde_json::to_value(openapi()).unwrap();
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(!paths.is_empty());
+
+        for (path, operations) in paths {
+            let documented: BTreeSet<String> = operations
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(|method| method.to_uppercase())
+                .collect();
+
+            // Path parameters get a sample value
+            let uri = path
+                .split('/')
+                .map(|segment| if segment.starts_with('{') { "sample" } else { segment })
+                .collect::<Vec<_>>()
+                .join("/");
+            let request = Request::builder()
+                .method(Method::TRACE)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{} is not routed", path);
+
+            // axum answers HEAD wherever it answers GET
+            let served: BTreeSet<String> = response.headers()[header::ALLOW]
+                .to_str()
+                .unwrap()
+                .split(',')
+                .map(|method| method.trim().to_string())
+                .filter(|method| method != "HEAD")
+                .collect();
+            assert_eq!(served, documented, "methods of {}", path);
+        }
+
+        let request = Request::builder().uri("/undocumented").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,14 +1,22 @@
 use std::io::{self, Write};
+use std::sync::Arc;
+use crate::config::PromptLimitsConfig;
 use crate::llms::{LlmModel, model_collection::ModelCollection};
 
-pub async fn run(models: ModelCollection) -> Result<(), Box<dyn std::error::Error>> {
-    let mut current_model: Option<&Box<dyn LlmModel>> = None;
+pub async fn run(models: ModelCollection, prompt_limits: PromptLimitsConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mut current_model: Option<(String, Arc<dyn LlmModel>)> = None;
     
     println!("Chat mode started. Available commands:");
     println!("  /list              - List all available models");
     println!("  /select <name>     - Select a model by name");
+    println!("  /tokens <text>     - Count the tokens of a prompt for the selected model");
     println!("  /exit              - Exit the chat");
     println!();
+
+    if let Some((name, model)) = models.default_model() {
+        println!("Selected default model: {} ({})\n", name, model.provider());
+        current_model = Some((name, model));
+    }
     
     loop {
         print!("> ");
@@ -27,7 +35,22 @@ pub async fn run(models: ModelCollection) -> Result<(), Box<dyn std::error::Erro
             
             "/list" => {
                 println!("\nAvailable models:");
-                for (name, provider, model_name) in models.list_models() {
+                let mut list = models.list_models();
+                list.sort_by(|a, b| a.0.cmp(b.0));
+                for (name, provider, model_name) in list {
+                    let default = if models.is_default(name) { ", default" } else { "" };
+                    println!("- {} ({} / {}{})", name, provider, model_name, default);
+                    let aliases = models.aliases_of(name);
+                    if !aliases.is_empty() {
+                        println!("    also: {}", aliases.join(", "));
+                    }
+                    let summary = models.get_model(name).map(|model| model.capabilities().summary()).unwrap_or_default();
+                    if !summary.is_empty() {
+                        println!("    {}", summary);
+                    }
+                }
+                println!("\nEmbedding models (available through the API):");
+                for (name, provider, model_name) in models.list_embedding_models() {
                     println!("- {} ({} / {})", name, provider, model_name);
                 }
                 println!();
@@ -35,26 +58,52 @@ pub async fn run(models: ModelCollection) -> Result<(), Box<dyn std::error::Erro
             
             input if input.starts_with("/select ") => {
                 let model_name = input.trim_start_matches("/select ").trim();
-                match models.get_model(model_name) {
-                    Some(model) => {
-                        current_model = Some(model);
+                match models.find_model(model_name) {
+                    Some((name, model)) => {
                         println!("\nSelected model: {} ({})\n", 
                             model.model_name(), model.provider());
+                        current_model = Some((name, model));
                     }
-                    None => println!("\nModel '{}' not found. Use /list to see available models.\n", 
-                        model_name),
+                    None => println!("\n{}\nUse /list to see available models.\n",
+                        models.model_not_found(model_name)),
                 }
             }
             
-            _ => {
-                match current_model {
-                    Some(model) => {
-                        let response = model.query(input).await?;
-                        println!("\n{}\n", response);
+            input if input == "/tokens" || input.starts_with("/tokens ") => {
+                let text = input["/tokens".len()..].trim();
+                match &current_model {
+                    Some(_) if text.is_empty() => println!("\nUsage: /tokens <text>\n"),
+                    Some((name, model)) => {
+                        let tokenizer = model.tokenizer();
+                        let count = tokenizer.count(text);
+                        let approximate = if tokenizer.is_exact() { "" } else { ", approximate" };
+                        print!("\n{} tokens ({}{})", count, tokenizer.encoding(), approximate);
+                        match prompt_limits.max_prompt_tokens(model.as_ref()) {
+                            Some(max) => println!(", {} takes up to {} prompt tokens\n", name, max),
+                            None => println!("\n"),
+                        }
                     }
                     None => println!("\nPlease select a model first using /select <name>\n"),
                 }
             }
+            
+            _ => {
+                match &current_model {
+                    Some((name, model)) => match prompt_limits.fit(input, name, model.as_ref()) {
+                        Err(message) => println!("\n{}\n", message),
+                        Ok(prompt) => match model.query(&prompt).await {
+                            Ok(completion) => {
+                                println!("\n{}\n", completion.text);
+                                if completion.model != model.model_name() {
+                                    println!("(answered by {} / {})\n", completion.provider, completion.model);
+                                }
+                            }
+                            Err(e) => println!("\nError: {}\n", e),
+                        },
+                    },
+                    None => println!("\nPlease select a model first using /select <name>\n"),
+                }
+            }
         }
     }
     
@@ -0,0 +1,242 @@
+use clap::Subcommand;
+use serde_json::{Map, Value};
+use std::fs;
+use toml_edit::{DocumentMut, InlineTable, Item, Table};
+use crate::config::source::{self, Format};
+use crate::config::{Config, ConfigError};
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the effective configuration, including defaults
+    Show,
+    /// Check the config file and report every problem found
+    Validate,
+    /// Print the location of the config file
+    Path,
+    /// Set a value by its dotted key, e.g. `cache.enabled true`; `null` resets it to the default
+    Set {
+        key: String,
+        value: String,
+    },
+    /// Write a config file with the default settings
+    Init {
+        /// Overwrite an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+pub fn run(command: &ConfigCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Config::config_path();
+
+    match command {
+        ConfigCommand::Show => {
+            println!("{}", serde_json::to_string_pretty(&Config::load()?)?);
+        }
+        ConfigCommand::Validate => {
+            Config::load()?;
+            if path.exists() {
+                println!("{} is valid", path.display());
+            } else {
+                println!("{} does not exist, the defaults are used", path.display());
+            }
+        }
+        ConfigCommand::Path => {
+            println!("{}", path.display());
+        }
+        ConfigCommand::Set { key, value } => {
+            set(key, value)?;
+            println!("Set {} in {}", key, path.display());
+        }
+        ConfigCommand::Init { force } => {
+            if path.exists() && !force {
+                return Err(format!("{} already exists, use --force to overwrite it", path.display()).into());
+            }
+            Config::default().save()?;
+            println!("Wrote default configuration to {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets `key` in the config file, keeping everything else as written. The
+/// value is taken as JSON when it parses as JSON and as a string otherwise.
+/// The file is only written when the result is a valid config. Variables and
+/// includes are left as written; only the check sees them resolved.
+///
+/// TOML files are edited in place, keeping comments and layout. JSON and
+/// YAML files are rewritten in their format with their keys in order; YAML
+/// files with comments are refused, as rewriting them would drop those.
+pub fn set(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let path = Config::config_path();
+    let content = if path.exists() {
+        fs::read_to_string(&path).map_err(|error| ConfigError::Read { path: path.clone(), error })?
+    } else {
+        String::new()
+    };
+
+    let value = match value {
+        "null" => None,
+        value => Some(serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))),
+    };
+
+    let content = match Format::of(&path) {
+        Format::Toml => {
+            source::parse_document(&path, &content)?;
+            let mut document: DocumentMut = content.parse()
+                .map_err(|e| format!("Cannot set {}: {}", key, e))?;
+            set_item(document.as_item_mut(), key, value)?;
+            document.to_string()
+        }
+        format => {
+            if format == Format::Yaml && content.lines().any(|line| line.trim_start().starts_with('#') || line.contains(" #")) {
+                return Err(format!(
+                    "Cannot set {}: {} has comments, which rewriting it would drop; edit the file instead",
+                    key,
+                    path.display(),
+                ).into());
+            }
+            let mut document = if content.is_empty() {
+                Value::Object(Map::new())
+            } else {
+                source::parse_document(&path, &content)?
+            };
+            set_value(&mut document, key, value)?;
+            source::serialize_document(&path, &document)
+                .map_err(|e| format!("Cannot set {}: {}", key, e))?
+        }
+    };
+
+    Config::from_content(&path, &content).map_err(|e| match e {
+        e @ ConfigError::Invalid { .. } => e.to_string(),
+        e => format!("Cannot set {}: {}", key, e),
+    })?;
+
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, content)?;
+    Ok(())
+}
+
+/// Like `set_value`, for a TOML document edited in place. Missing tables
+/// are created; a replaced value keeps the comment after it.
+fn set_item(document: &mut Item, key: &str, value: Option<Value>) -> Result<(), String> {
+    let (parents, last) = match key.rsplit_once('.') {
+        Some((parents, last)) => (parents.split('.').collect(), last),
+        None => (Vec::new(), key),
+    };
+
+    let mut current = document;
+    for segment in parents {
+        if current.is_array() || current.is_array_of_tables() {
+            current = segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| current.get_mut(index))
+                .ok_or_else(|| format!("'{}' in '{}' is not an index of the list", segment, key))?;
+            continue;
+        }
+
+        let inline = current.is_inline_table();
+        let table = current
+            .as_table_like_mut()
+            .ok_or_else(|| format!("'{}' in '{}' is not an object", segment, key))?;
+        if table.get(segment).is_none() {
+            let child = if inline {
+                Item::Value(InlineTable::new().into())
+            } else {
+                let mut child = Table::new();
+                child.set_implicit(true);
+                Item::Table(child)
+            };
+            table.insert(segment, child);
+        }
+        current = table.get_mut(segment).expect("inserted above");
+    }
+
+    let value = value.map(|value| toml_value(key, &value)).transpose()?;
+    if current.is_array() || current.is_array_of_tables() {
+        let item = last
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| current.get_mut(index))
+            .ok_or_else(|| format!("'{}' in '{}' is not an index of the list", last, key))?;
+        let value = value.ok_or_else(|| format!("'{}' is a list entry, which TOML cannot leave empty", key))?;
+        replace(item, value);
+        return Ok(());
+    }
+
+    let table = current
+        .as_table_like_mut()
+        .ok_or_else(|| format!("'{}' cannot be set, its parent is not an object", key))?;
+    match (table.get_mut(last), value) {
+        (Some(item), Some(value)) => replace(item, value),
+        (None, Some(value)) => {
+            table.insert(last, Item::Value(value));
+        }
+        (_, None) => {
+            table.remove(last);
+        }
+    }
+    Ok(())
+}
+
+/// Puts `value` in place of `item`, keeping the comments around a replaced
+/// value.
+fn replace(item: &mut Item, mut value: toml_edit::Value) {
+    if let Some(existing) = item.as_value() {
+        *value.decor_mut() = existing.decor().clone();
+    }
+    *item = Item::Value(value);
+}
+
+fn toml_value(key: &str, value: &Value) -> Result<toml_edit::Value, String> {
+    toml::Value::try_from(value)
+        .map_err(|e| format!("Cannot set {}: {}", key, e))?
+        .to_string()
+        .parse()
+        .map_err(|e| format!("Cannot set {}: {}", key, e))
+}
+
+/// Walks the dotted `key` through objects and array indices, creating
+/// missing objects on the way. `None` removes the last key.
+fn set_value(document: &mut Value, key: &str, value: Option<Value>) -> Result<(), String> {
+    let (parents, last) = match key.rsplit_once('.') {
+        Some((parents, last)) => (parents.split('.').collect(), last),
+        None => (Vec::new(), key),
+    };
+
+    let mut current = document;
+    for segment in parents {
+        current = match current {
+            Value::Object(map) => map.entry(segment).or_insert_with(|| Value::Object(Map::new())),
+            Value::Array(items) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get_mut(index))
+                .ok_or_else(|| format!("'{}' in '{}' is not an index of the list", segment, key))?,
+            _ => return Err(format!("'{}' in '{}' is not an object", segment, key)),
+        };
+    }
+
+    match (current, value) {
+        (Value::Object(map), Some(value)) => {
+            map.insert(last.to_string(), value);
+        }
+        (Value::Object(map), None) => {
+            map.remove(last);
+        }
+        (Value::Array(items), value) => {
+            let item = last
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| items.get_mut(index))
+                .ok_or_else(|| format!("'{}' in '{}' is not an index of the list", last, key))?;
+            *item = value.unwrap_or(Value::Null);
+        }
+        _ => return Err(format!("'{}' cannot be set, its parent is not an object", key)),
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
 pub mod api;
 pub mod chat;
-pub mod service;
\ No newline at end of file
+pub mod service;pub mod serve;
+pub mod config_command;
@@ -0,0 +1,207 @@
+#[cfg(unix)]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use sd_notify::NotifyState;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+#[cfg(unix)]
+use tracing::{error, info, warn};
+#[cfg(unix)]
+use crate::config::Config;
+#[cfg(unix)]
+use crate::modes::api;
+#[cfg(unix)]
+use crate::reload::{self, ModelRegistry};
+#[cfg(unix)]
+use crate::server;
+
+/// Default location of the unit written by `install-systemd`.
+#[cfg(unix)]
+pub const DEFAULT_UNIT_PATH: &str = "/etc/systemd/system/llmapi-rust.service";
+
+#[cfg(unix)]
+const PIDFILE_PATH: &str = "/run/llmapi-rust/llmapi-rust.pid";
+
+/// Runs the API server as a systemd service: reports readiness, reloads and
+/// stopping over `sd_notify`, pings the watchdog, reloads the models on
+/// SIGHUP and stops gracefully on SIGTERM or SIGINT.
+#[cfg(unix)]
+pub async fn run(config: Config, pidfile: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let _pidfile = pidfile.map(PidFile::create).transpose()?;
+
+    let listeners = server::bind(&config).await?;
+    info!("Starting API server");
+
+    let registry = api::start_registry(&config).await;
+    if config.watch_config {
+        tokio::spawn(reload::watch_config_file(registry.clone(), api::CONFIG_POLL_INTERVAL));
+    }
+    tokio::spawn(reload_on_hangup(registry.clone()));
+
+    let shutdown = async {
+        crate::shutdown::signal().await;
+        notify(&[NotifyState::Stopping]);
+    };
+
+    notify(&[NotifyState::Ready]);
+    start_watchdog();
+    api::serve(listeners, registry, config, shutdown).await
+}
+
+/// Reloads on SIGHUP, telling systemd while the reload is in progress. A
+/// rejected config leaves the previous models serving.
+#[cfg(unix)]
+async fn reload_on_hangup(registry: Arc<ModelRegistry>) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!(error = %e, "Failed to listen for SIGHUP");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        notify_reloading();
+        match registry.reload_blocking("SIGHUP").await {
+            Ok(_) => notify(&[NotifyState::Ready]),
+            Err(_) => notify(&[NotifyState::Ready, NotifyState::Status("Config reload rejected, see log")]),
+        }
+    }
+}
+
+/// Sends a state update to systemd. Does nothing when not started by
+/// systemd with `Type=notify`.
+#[cfg(unix)]
+fn notify(state: &[NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, state) {
+        warn!(error = %e, "Failed to notify systemd");
+    }
+}
+
+#[cfg(unix)]
+fn notify_reloading() {
+    match NotifyState::monotonic_usec_now() {
+        Ok(now) => notify(&[NotifyState::Reloading, now]),
+        Err(_) => notify(&[NotifyState::Reloading]),
+    }
+}
+
+/// Pings the systemd watchdog at half the configured interval, so systemd
+/// restarts the service when the runtime stops making progress.
+#[cfg(unix)]
+fn start_watchdog() {
+    let mut usec = 0;
+    if !sd_notify::watchdog_enabled(false, &mut usec) {
+        return;
+    }
+
+    let period = Duration::from_micros(usec / 2);
+    info!(interval_ms = period.as_millis() as u64, "Watchdog enabled");
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        loop {
+            ticker.tick().await;
+            notify(&[NotifyState::Watchdog]);
+        }
+    });
+}
+
+/// Holds the pidfile for as long as the process runs.
+#[cfg(unix)]
+struct PidFile(PathBuf);
+
+#[cfg(unix)]
+impl PidFile {
+    fn create(path: PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, format!("{}\n", std::process::id()))
+            .map_err(|e| format!("Failed to write pidfile {}: {}", path.display(), e))?;
+        Ok(Self(path))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Writes a systemd unit running `serve` with the current executable, or
+/// prints it when `path` is `-`. The service uses the config file and
+/// profile in effect now, as it may run as a user with another config
+/// directory.
+#[cfg(unix)]
+pub fn install_systemd(path: &Path, user: Option<&str>, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let executable = std::env::current_exe()?;
+    let mut arguments = vec![systemd_quote(&executable.display().to_string()), "serve".to_string()];
+    let config_path = Config::config_path();
+    if config_path.exists() {
+        arguments.push("--config".to_string());
+        arguments.push(systemd_quote(&std::path::absolute(&config_path)?.display().to_string()));
+    }
+    if let Some(profile) = Config::profile() {
+        arguments.push("--profile".to_string());
+        arguments.push(systemd_quote(profile));
+    }
+    arguments.push("--pidfile".to_string());
+    arguments.push(systemd_quote(PIDFILE_PATH));
+
+    let user_line = user.map(|user| format!("User={}\n", user)).unwrap_or_default();
+    let unit = format!(
+        "[Unit]
+Description=LLM API gateway
+After=network-online.target
+Wants=network-online.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart={exec_start}
+ExecReload=/bin/kill -HUP $MAINPID
+PIDFile={pidfile}
+RuntimeDirectory=llmapi-rust
+{user_line}EnvironmentFile=-/etc/llmapi-rust/environment
+Restart=on-failure
+WatchdogSec=30
+TimeoutStopSec={stop_timeout}
+
+[Install]
+WantedBy=multi-user.target
+",
+        exec_start = arguments.join(" "),
+        pidfile = PIDFILE_PATH,
+        user_line = user_line,
+        stop_timeout = config.shutdown_timeout_secs + 10,
+    );
+
+    if path == Path::new("-") {
+        print!("{}", unit);
+        return Ok(());
+    }
+
+    fs::write(path, unit).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    println!("Unit file written to {}", path.display());
+    println!("Enable and start it with: systemctl daemon-reload && systemctl enable --now llmapi-rust");
+    Ok(())
+}
+
+/// Quotes an `ExecStart` argument, escaping what systemd would otherwise
+/// expand: `%` specifiers and `$` variables.
+#[cfg(unix)]
+fn systemd_quote(argument: &str) -> String {
+    let escaped = argument
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{}\"", escaped)
+}
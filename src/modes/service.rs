@@ -3,14 +3,14 @@ use std::{
     ffi::OsString,
     time::Duration,
     path::PathBuf,
-    fs::OpenOptions,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
 };
 
 #[cfg(windows)]
-use log::{info, error};
-#[cfg(windows)]
-use simplelog::{WriteLogger, LevelFilter, Config};
+use tracing::{info, error};
 #[cfg(windows)]
 use windows_service::{
     define_windows_service,
@@ -22,54 +22,35 @@ use windows_service::{
     service_dispatcher,
 };
 #[cfg(windows)]
-use crate::llms::model_collection::ModelCollection;
+use crate::config::Config as AppConfig;
 
 #[cfg(windows)]
 const SERVICE_NAME: &str = "LlmApiService";
 #[cfg(windows)]
 const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
 
+/// The config as resolved by `main`, command-line overrides included, for
+/// the service thread the dispatcher starts.
 #[cfg(windows)]
-static mut SERVICE_PORT: u16 = 3000; // Default port
+static SERVICE_CONFIG: OnceLock<AppConfig> = OnceLock::new();
 
 #[cfg(windows)]
 static SERVICE_RUNNING: AtomicBool = AtomicBool::new(true);
 
+/// Log file used by the service when the config does not name one.
 #[cfg(windows)]
-fn setup_logging() -> Result<(), Box<dyn std::error::Error>> {
-    let exe_path = std::env::current_exe()?;
-    let log_path = exe_path.parent().unwrap_or(&PathBuf::from(".")).join("llm-api.log");
-    
-    let log_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .write(true)
-        .open(log_path.clone())?;
-
-    WriteLogger::init(
-        LevelFilter::Info,
-        Config::default(),
-        log_file,
-    )?;
-    
-    info!("Logging initialized at {}", log_path.display());
-    Ok(())
+pub fn default_log_path() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe_path| exe_path.parent().map(|parent| parent.to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("llm-api.log")
 }
 
 #[cfg(windows)]
-pub fn run(port: u16) -> windows_service::Result<()> {
-    unsafe {
-        SERVICE_PORT = port;
-    }
-    
-    if let Err(e) = setup_logging() {
-        error!("Failed to initialize logging: {}", e);
-        eprintln!("Failed to initialize logging: {}", e);
-        let error = windows_service::Error::LaunchArgumentsNotSupported;
-        return Err(error);
-    }
-    
-    info!("Starting {} on port {}", SERVICE_NAME, port);
+pub fn run(config: AppConfig) -> windows_service::Result<()> {
+    info!("Starting {} on port {}", SERVICE_NAME, config.port);
+    let _ = SERVICE_CONFIG.set(config);
     service_dispatcher::start(SERVICE_NAME, ffi_service_main)
 }
 
@@ -120,17 +101,23 @@ fn run_service(_arguments: Vec<OsString>) -> windows_service::Result<()> {
     let runtime = tokio::runtime::Runtime::new()
         .map_err(|_e| windows_service::Error::LaunchArgumentsNotSupported)?;
     
-    let models = ModelCollection::new();
-    let port = unsafe { SERVICE_PORT };
+    let config = SERVICE_CONFIG.get().cloned().ok_or_else(|| {
+        error!("Service started without a config");
+        windows_service::Error::LaunchArgumentsNotSupported
+    })?;
     
-    info!("Starting API server on port {}", port);
+    info!("Starting API server on port {}", config.port);
     
     // Create a shutdown signal
-    let (shutdown_tx, _shutdown_rx) = tokio::sync::oneshot::channel();
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let shutdown = async move {
+        let _ = shutdown_rx.await;
+    };
+    let drain_timeout = Duration::from_secs(config.shutdown_timeout_secs);
     
     // Spawn the API server task
     let server_handle = runtime.spawn(async move {
-        if let Err(e) = crate::modes::api::run(models, port).await {
+        if let Err(e) = crate::modes::api::run(config, shutdown).await {
             error!("API server error: {}", e);
             eprintln!("API server error: {}", e);
         }
@@ -148,7 +135,7 @@ fn run_service(_arguments: Vec<OsString>) -> windows_service::Result<()> {
                 controls_accepted: ServiceControlAccept::empty(),
                 exit_code: ServiceExitCode::Win32(0),
                 checkpoint: 0,
-                wait_hint: Duration::from_secs(10),
+                wait_hint: drain_timeout + Duration::from_secs(5),
                 process_id: None,
             });
         }
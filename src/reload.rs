@@ -0,0 +1,164 @@
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+use tracing::{error, info};
+use crate::config::{Config, ConfigError};
+use crate::llms::discovery::Catalog;
+use crate::llms::model_collection::ModelCollection;
+
+/// The models served by the API. A reload builds a new collection from the
+/// config file and swaps it in whole; requests already running keep the
+/// models they started with. Models whose settings did not change are
+/// taken over with their circuit breaker and ejection state. Models discovered from the providers are kept
+/// across reloads and refreshed on their own schedule.
+pub struct ModelRegistry {
+    current: RwLock<Arc<ModelCollection>>,
+    config: RwLock<Arc<Config>>,
+    catalog: RwLock<Catalog>,
+    /// Wakes the discovery refresh when a reload changed its settings.
+    discovery_changed: Notify,
+    /// Problems that made the last reload fail; empty once one succeeds.
+    reload_errors: RwLock<Vec<String>>,
+}
+
+impl ModelRegistry {
+    pub fn new(config: &Config, catalog: Catalog) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(ModelCollection::new(config, &catalog, None))),
+            config: RwLock::new(Arc::new(config.clone())),
+            catalog: RwLock::new(catalog),
+            discovery_changed: Notify::new(),
+            reload_errors: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Why the last reload failed, if it did. The models of the config
+    /// loaded before it are still being served.
+    pub fn reload_errors(&self) -> Vec<String> {
+        self.reload_errors.read().unwrap().clone()
+    }
+
+    pub fn models(&self) -> Arc<ModelCollection> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Loads and validates the config file and swaps in the models built
+    /// from it, returning how many models are registered. A config that
+    /// fails to load or validate is not applied.
+    pub fn reload(&self, trigger: &str) -> Result<usize, Vec<String>> {
+        let result = Config::load().map_err(|e| match e {
+            ConfigError::Invalid { errors, .. } => errors,
+            e => vec![e.to_string()],
+        });
+
+        let config = match result {
+            Ok(config) => config,
+            Err(errors) => {
+                for e in &errors {
+                    error!(trigger, error = %e, "Config reload rejected");
+                }
+                *self.reload_errors.write().unwrap() = errors.clone();
+                return Err(errors);
+            }
+        };
+
+        let previous = std::mem::replace(&mut *self.config.write().unwrap(), Arc::new(config));
+        if previous.discovery != self.config.read().unwrap().discovery {
+            self.discovery_changed.notify_one();
+        }
+
+        let count = self.rebuild();
+        self.reload_errors.write().unwrap().clear();
+        info!(trigger, models = count, "Config reloaded");
+        Ok(count)
+    }
+
+    /// Runs `reload` on the blocking thread pool, as reading the config and
+    /// building the models block.
+    pub async fn reload_blocking(self: &Arc<Self>, trigger: &'static str) -> Result<usize, Vec<String>> {
+        let registry = self.clone();
+        tokio::task::spawn_blocking(move || registry.reload(trigger))
+            .await
+            .unwrap_or_else(|e| Err(vec![format!("Reload failed: {}", e)]))
+    }
+
+    /// Asks the providers for their models and, if the lists changed, swaps
+    /// in models built from them.
+    pub async fn discover(&self, trigger: &str) {
+        let config = self.config.read().unwrap().clone();
+        let previous = self.catalog.read().unwrap().clone();
+        let catalog = Catalog::fetch(&config, &previous).await;
+        if catalog == previous {
+            return;
+        }
+
+        *self.catalog.write().unwrap() = catalog;
+        let count = self.rebuild();
+        info!(trigger, models = count, "Discovered models updated");
+    }
+
+    /// Builds the models from the current config and catalog. Holding the
+    /// write lock while building keeps a reload and a discovery running at
+    /// the same time from swapping in an outdated collection last.
+    fn rebuild(&self) -> usize {
+        let mut current = self.current.write().unwrap();
+        let config = self.config.read().unwrap().clone();
+        let models = ModelCollection::new(&config, &self.catalog.read().unwrap(), Some(&current));
+        let count = models.list_models().len();
+        *current = Arc::new(models);
+        count
+    }
+}
+
+/// Fetches the providers' model lists again every `discovery.refresh_secs`
+/// and whenever a reload changes the discovery settings.
+pub async fn refresh_discovered_models(registry: Arc<ModelRegistry>) {
+    loop {
+        let refresh_secs = registry.config.read().unwrap().discovery.refresh_secs;
+        if refresh_secs == 0 {
+            registry.discovery_changed.notified().await;
+        } else {
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(refresh_secs)) => {}
+                _ = registry.discovery_changed.notified() => {}
+            }
+        }
+        registry.discover("refresh").await;
+    }
+}
+
+/// Reloads whenever the config file's modification time changes. Polling
+/// also notices editors that replace the file instead of writing to it.
+pub async fn watch_config_file(registry: Arc<ModelRegistry>, interval: Duration) {
+    let path = Config::config_path();
+    let modified = || std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+
+    let mut last_modified: Option<SystemTime> = modified();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        let current = modified();
+        if current != last_modified {
+            last_modified = current;
+            let _ = registry.reload_blocking("file change").await;
+        }
+    }
+}
+
+/// Reloads on every SIGHUP.
+#[cfg(unix)]
+pub async fn reload_on_hangup(registry: Arc<ModelRegistry>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!(error = %e, "Failed to listen for SIGHUP");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        let _ = registry.reload_blocking("SIGHUP").await;
+    }
+}
@@ -0,0 +1,215 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use crate::llms::QueryError;
+use crate::llms::error::{ErrorClass, ProviderError};
+
+/// Shown instead of a secret written into the config file.
+const REDACTED: &str = "<redacted>";
+
+/// A credential in the config file. `file:PATH` reads it from a file,
+/// `env:NAME` from an environment variable and `exec:COMMAND` from the
+/// output of a command; anything else is the credential itself.
+///
+/// Serializing or formatting a secret never reveals a credential written
+/// into the config: it shows as `<redacted>`, references as written.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Secret {
+    Value(String),
+    File(PathBuf),
+    Env(String),
+    Exec(String),
+}
+
+impl Secret {
+    pub fn parse(value: &str) -> Self {
+        if let Some(path) = value.strip_prefix("file:") {
+            Secret::File(PathBuf::from(path))
+        } else if let Some(name) = value.strip_prefix("env:") {
+            Secret::Env(name.to_string())
+        } else if let Some(command) = value.strip_prefix("exec:") {
+            Secret::Exec(command.to_string())
+        } else {
+            Secret::Value(value.to_string())
+        }
+    }
+
+    /// Checks that a reference names something to read from.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Secret::File(path) if path.as_os_str().is_empty() => Err("file: needs a path".to_string()),
+            Secret::Env(name) if name.is_empty() => Err("env: needs a variable name".to_string()),
+            Secret::Exec(command) if command.trim().is_empty() => Err("exec: needs a command".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Secret::Value(_) => f.write_str(REDACTED),
+            Secret::File(path) => write!(f, "file:{}", path.display()),
+            Secret::Env(name) => write!(f, "env:{}", name),
+            Secret::Exec(command) => write!(f, "exec:{}", command),
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Secret::parse(&String::deserialize(deserializer)?))
+    }
+}
+
+/// Masks words in a line of the config file that look like credentials, for
+/// showing the line in an error. Such lines are shown before the file has
+/// parsed, so which values are secrets is not known yet.
+pub fn redact_credentials(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, result: &mut String| {
+        let looks_like_credential = word.len() >= 20
+            && word.chars().any(|c| c.is_ascii_digit())
+            && word.chars().any(|c| c.is_ascii_alphabetic())
+            && !word.contains("://")
+            && !word.starts_with('/');
+        result.push_str(if looks_like_credential { REDACTED } else { word });
+        word.clear();
+    };
+
+    for c in line.chars() {
+        if c.is_whitespace() || "\"',{}[]".contains(c) {
+            flush(&mut word, &mut result);
+            result.push(c);
+        } else {
+            word.push(c);
+        }
+    }
+    flush(&mut word, &mut result);
+    result
+}
+
+/// An API key as used by a provider. Keys read from a file or a command are
+/// kept for `refresh` and then read again, so rotated keys are picked up
+/// without a restart; when reading again fails, the previous key stays in
+/// use. Keys from environment variables are read on every use.
+#[derive(Clone)]
+pub struct ApiKey {
+    source: Secret,
+    refresh: Duration,
+    exec_timeout: Duration,
+    cached: Arc<Mutex<Option<(String, Instant)>>>,
+}
+
+impl ApiKey {
+    pub fn new(source: Secret, refresh: Duration, exec_timeout: Duration) -> Self {
+        Self {
+            source,
+            refresh,
+            exec_timeout,
+            cached: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// A key known up front, e.g. taken from the provider's environment
+    /// variable.
+    pub fn from_value(value: String) -> Self {
+        Self::new(Secret::Value(value), Duration::ZERO, Duration::ZERO)
+    }
+
+    /// The current key. Errors describe where the key was read from but
+    /// never contain it.
+    pub async fn value(&self) -> Result<String, QueryError> {
+        match &self.source {
+            Secret::Value(value) => return Ok(value.clone()),
+            Secret::Env(name) => {
+                return std::env::var(name)
+                    .map_err(|_| key_error(format!("environment variable {} is not set", name)));
+            }
+            Secret::File(_) | Secret::Exec(_) => {}
+        }
+
+        let mut cached = self.cached.lock().await;
+        if let Some((value, read_at)) = cached.as_ref() {
+            if read_at.elapsed() < self.refresh {
+                return Ok(value.clone());
+            }
+        }
+
+        match self.read().await {
+            Ok(value) => {
+                *cached = Some((value.clone(), Instant::now()));
+                Ok(value)
+            }
+            Err(e) => match cached.as_mut() {
+                Some((value, read_at)) => {
+                    tracing::warn!(source = %self.source, error = %e, "Failed to refresh API key, keeping the previous one");
+                    *read_at = Instant::now();
+                    Ok(value.clone())
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    async fn read(&self) -> Result<String, QueryError> {
+        let value = match &self.source {
+            Secret::File(path) => tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| key_error(format!("cannot read {}: {}", path.display(), e)))?,
+            Secret::Exec(command) => self.run(command).await?,
+            _ => unreachable!("only files and commands are read"),
+        };
+
+        let value = value.trim().to_string();
+        if value.is_empty() {
+            return Err(key_error(format!("{} yielded an empty key", self.source)));
+        }
+        Ok(value)
+    }
+
+    /// Runs `command` through the shell and takes its output as the key.
+    /// Neither its output nor its error output end up in errors or logs.
+    async fn run(&self, command: &str) -> Result<String, QueryError> {
+        #[cfg(windows)]
+        let mut process = tokio::process::Command::new("cmd");
+        #[cfg(windows)]
+        process.arg("/C").arg(command);
+        #[cfg(not(windows))]
+        let mut process = tokio::process::Command::new("sh");
+        #[cfg(not(windows))]
+        process.arg("-c").arg(command);
+
+        process.stdin(std::process::Stdio::null()).kill_on_drop(true);
+        let output = tokio::time::timeout(self.exec_timeout, process.output())
+            .await
+            .map_err(|_| key_error(format!("exec:{} timed out after {:?}", command, self.exec_timeout)))?
+            .map_err(|e| key_error(format!("cannot run exec:{}: {}", command, e)))?;
+
+        if !output.status.success() {
+            return Err(key_error(format!("exec:{} failed with {}", command, output.status)));
+        }
+        String::from_utf8(output.stdout)
+            .map_err(|_| key_error(format!("exec:{} did not print UTF-8", command)))
+    }
+}
+
+fn key_error(message: String) -> QueryError {
+    ProviderError::new(ErrorClass::Auth, format!("No API key: {}", message)).into()
+}
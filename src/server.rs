@@ -0,0 +1,280 @@
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tracing::{debug, error, info, warn};
+use crate::config::{Config, TlsConfig};
+
+/// How long a client may take to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A socket the API server accepts connections on.
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixSocket),
+}
+
+impl Listener {
+    fn describe(&self, tls: bool) -> String {
+        let scheme = if tls { "https" } else { "http" };
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(address) => format!("{}://{}", scheme, address),
+                Err(_) => format!("{}://?", scheme),
+            },
+            #[cfg(unix)]
+            Listener::Unix(socket) => format!("{}+unix://{}", scheme, socket.path.display()),
+        }
+    }
+}
+
+/// A Unix domain socket, removed again when dropped.
+#[cfg(unix)]
+pub struct UnixSocket {
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Opens the configured Unix socket, or else listens on every address
+/// `bind_address` resolves to, e.g. both `127.0.0.1` and `::1` for
+/// `localhost`.
+pub async fn bind(config: &Config) -> Result<Vec<Listener>, Box<dyn std::error::Error>> {
+    #[cfg(unix)]
+    if let Some(path) = &config.unix_socket {
+        return Ok(vec![Listener::Unix(bind_unix(path.clone())?)]);
+    }
+    #[cfg(not(unix))]
+    if config.unix_socket.is_some() {
+        return Err("Unix sockets are only supported on Linux and macOS".into());
+    }
+
+    let mut addresses: Vec<SocketAddr> = tokio::net::lookup_host((config.bind_address.as_str(), config.port))
+        .await
+        .map_err(|e| format!("Cannot resolve bind address {}: {}", config.bind_address, e))?
+        .collect();
+    addresses.sort();
+    addresses.dedup();
+
+    let mut listeners = Vec::new();
+    for address in addresses {
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|e| format!("Cannot listen on {}: {}", address, e))?;
+        listeners.push(Listener::Tcp(listener));
+    }
+    Ok(listeners)
+}
+
+#[cfg(unix)]
+fn bind_unix(path: PathBuf) -> Result<UnixSocket, Box<dyn std::error::Error>> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket left behind by a process that did not shut down cleanly
+    // would make binding fail, so it is removed once connecting to it is
+    // refused. Live sockets and anything else at the path are left alone.
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if !metadata.file_type().is_socket() {
+            return Err(format!("{} exists and is not a socket", path.display()).into());
+        }
+        match std::os::unix::net::UnixStream::connect(&path) {
+            Ok(_) => return Err(format!("{} is already in use by another process", path.display()).into()),
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => std::fs::remove_file(&path)?,
+            Err(e) => return Err(format!("Cannot check whether {} is in use: {}", path.display(), e).into()),
+        }
+    }
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| format!("Cannot listen on {}: {}", path.display(), e))?;
+    Ok(UnixSocket { listener, path })
+}
+
+/// The certificate and key connections are accepted with. Swapped whole
+/// when the files change; handshakes already under way finish with the
+/// previous certificate.
+#[derive(Clone)]
+pub struct Tls {
+    acceptor: Arc<RwLock<TlsAcceptor>>,
+}
+
+impl Tls {
+    pub fn load(config: &TlsConfig) -> Result<Self, String> {
+        Ok(Self {
+            acceptor: Arc::new(RwLock::new(acceptor(config)?)),
+        })
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.read().unwrap().clone()
+    }
+
+    /// Loads the certificate and key again whenever one of the files'
+    /// modification times changes. Files that fail to load are logged and
+    /// the current certificate stays in use.
+    pub async fn watch(self, config: TlsConfig, interval: Duration) {
+        let modified = || {
+            [&config.cert, &config.key].map(|path| {
+                std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+            })
+        };
+
+        let mut last_modified: [Option<SystemTime>; 2] = modified();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let current = modified();
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+
+            match acceptor(&config) {
+                Ok(acceptor) => {
+                    *self.acceptor.write().unwrap() = acceptor;
+                    info!(cert = %config.cert.display(), "TLS certificate reloaded");
+                }
+                Err(e) => error!(error = %e, "TLS certificate reload rejected"),
+            }
+        }
+    }
+}
+
+fn acceptor(config: &TlsConfig) -> Result<TlsAcceptor, String> {
+    let certs = CertificateDer::pem_file_iter(&config.cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Cannot read certificates from {}: {}", config.cert.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("{} contains no certificates", config.cert.display()));
+    }
+    let key = PrivateKeyDer::from_pem_file(&config.key)
+        .map_err(|e| format!("Cannot read private key from {}: {}", config.key.display(), e))?;
+
+    let mut server_config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid certificate or key: {}", e))?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+/// Serves `router` on all `listeners` until `stop` turns true, then closes
+/// the listeners and lets open connections finish their requests.
+pub async fn serve(listeners: Vec<Listener>, tls: Option<Tls>, router: Router, stop: watch::Receiver<bool>) {
+    let mut accept_loops = JoinSet::new();
+    for listener in listeners {
+        info!("Listening on {}", listener.describe(tls.is_some()));
+        accept_loops.spawn(accept(listener, tls.clone(), router.clone(), stop.clone()));
+    }
+    while accept_loops.join_next().await.is_some() {}
+}
+
+async fn accept(listener: Listener, tls: Option<Tls>, router: Router, mut stop: watch::Receiver<bool>) {
+    let mut connections = JoinSet::new();
+    loop {
+        let accepted = tokio::select! {
+            accepted = next_connection(&listener, tls.clone(), router.clone(), stop.clone()) => accepted,
+            _ = stop.wait_for(|stop| *stop) => break,
+        };
+        match accepted {
+            Ok(connection) => {
+                connections.spawn(connection);
+            }
+            Err(e) => {
+                // Usually running out of file descriptors; pausing avoids spinning
+                warn!(error = %e, "Failed to accept connection");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+        while connections.try_join_next().is_some() {}
+    }
+
+    drop(listener);
+    while connections.join_next().await.is_some() {}
+}
+
+type Connection = std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>;
+
+async fn next_connection(
+    listener: &Listener,
+    tls: Option<Tls>,
+    router: Router,
+    stop: watch::Receiver<bool>,
+) -> std::io::Result<Connection> {
+    Ok(match listener {
+        Listener::Tcp(listener) => {
+            let (stream, _) = listener.accept().await?;
+            let _ = stream.set_nodelay(true);
+            Box::pin(serve_connection(stream, tls, router, stop))
+        }
+        #[cfg(unix)]
+        Listener::Unix(socket) => {
+            let (stream, _) = socket.listener.accept().await?;
+            Box::pin(serve_connection(stream, tls, router, stop))
+        }
+    })
+}
+
+async fn serve_connection<S>(stream: S, tls: Option<Tls>, router: Router, stop: watch::Receiver<bool>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let Some(tls) = tls else {
+        return serve_http(stream, router, stop).await;
+    };
+
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, tls.acceptor().accept(stream)).await {
+        Ok(Ok(stream)) => serve_http(stream, router, stop).await,
+        Ok(Err(e)) => debug!(error = %e, "TLS handshake failed"),
+        Err(_) => debug!("TLS handshake timed out"),
+    }
+}
+
+/// Serves HTTP/1.1 and HTTP/2 on one connection. Once `stop` turns true,
+/// the request in progress is finished and the connection closed.
+async fn serve_http<S>(stream: S, router: Router, mut stop: watch::Receiver<bool>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let connection = builder.serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(router));
+    tokio::pin!(connection);
+
+    tokio::select! {
+        result = connection.as_mut() => {
+            if let Err(e) = result {
+                debug!(error = %e, "Connection closed with an error");
+            }
+            return;
+        }
+        _ = stop.wait_for(|stop| *stop) => {}
+    }
+
+    connection.as_mut().graceful_shutdown();
+    if let Err(e) = connection.await {
+        debug!(error = %e, "Connection closed with an error");
+    }
+}